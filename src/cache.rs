@@ -0,0 +1,109 @@
+/* Copyright (c) 2025 Eric Hernandez  */
+
+//! The on-disk cache, given with `--cache`, that remembers facts about earlier runs.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+/// Facts remembered between runs.
+#[derive(Default)]
+pub struct Cache {
+	/// Hash of the license text that footers were last written with.
+	pub license_hash: Option<String>,
+	/// Files a run stopped by `--time-budget` did not get to, which the next run
+	/// starts with.
+	pub remaining: Option<Vec<PathBuf>>,
+	/// Hash of the settings of the run that left `remaining`, which only a run with the
+	/// same settings resumes.
+	pub remaining_hash: Option<String>,
+	/// When the last run that wrote files finished, in milliseconds since the Unix epoch.
+	pub last_run: Option<u64>,
+	/// Hash of the settings the last run was made with; see `--incremental`.
+	pub config_hash: Option<String>,
+}
+
+impl Cache {
+	/// Load the cache from `path`. A missing file yields an empty cache.
+	pub fn load(path: &Path) -> io::Result<Cache> {
+		let text = match fs::read_to_string(path) {
+			Ok(text) => text,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Cache::default()),
+			Err(e) => return Err(e),
+		};
+		let value: Value = serde_json::from_str(&text).map_err(|e| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("Invalid cache file {}: {}", path.display(), e),
+			)
+		})?;
+
+		Ok(Cache {
+			license_hash: value["license_hash"].as_str().map(str::to_string),
+			remaining: value["remaining"].as_array().map(|files| {
+				files
+					.iter()
+					.filter_map(Value::as_str)
+					.map(PathBuf::from)
+					.collect()
+			}),
+			remaining_hash: value["remaining_hash"].as_str().map(str::to_string),
+			last_run: value["last_run"].as_u64(),
+			config_hash: value["config_hash"].as_str().map(str::to_string),
+		})
+	}
+
+	/// Write the cache to `path`, replacing any previous contents.
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		let remaining = self.remaining.as_ref().map(|files| {
+			files
+				.iter()
+				.map(|file| file.to_string_lossy())
+				.collect::<Vec<_>>()
+		});
+		let value = json!({
+			"license_hash": self.license_hash,
+			"remaining": remaining,
+			"remaining_hash": self.remaining_hash,
+			"last_run": self.last_run,
+			"config_hash": self.config_hash,
+		});
+		fs::write(path, serde_json::to_string_pretty(&value).unwrap() + "\n")
+	}
+}
+
+/// Returns a stable hex digest of `text` (64-bit FNV-1a), used to notice changes
+/// between runs without storing the text itself.
+pub fn hash(text: &str) -> String {
+	let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+	for byte in text.bytes() {
+		hash ^= u64::from(byte);
+		hash = hash.wrapping_mul(0x0100_0000_01b3);
+	}
+	format!("{:016x}", hash)
+}
+
+/*
+ * License:
+ * Copyright (c) 2025 Eric Hernandez
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
@@ -0,0 +1,133 @@
+/* Copyright (c) 2025 Eric Hernandez  */
+
+//! Fetching the license text from `--license-url`, with an optional on-disk cache
+//! given by `--license-cache-dir` so repeated runs do not download it again.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// A directory of fetched license texts, one file per URL.
+pub struct LicenseCache {
+	dir: PathBuf,
+	/// How long a cached copy is used before it is fetched again.
+	ttl: Duration,
+}
+
+impl LicenseCache {
+	pub fn new(dir: &Path, ttl: Duration) -> LicenseCache {
+		LicenseCache {
+			dir: dir.to_path_buf(),
+			ttl,
+		}
+	}
+
+	/// The cache file for `url`.
+	fn entry(&self, url: &str) -> PathBuf {
+		self.dir.join(format!("{}.txt", crate::cache::hash(url)))
+	}
+
+	/// The cached text for `url`, unless there is none or it is older than the TTL.
+	fn get(&self, url: &str) -> Option<String> {
+		let entry = self.entry(url);
+		let modified = fs::metadata(&entry).ok()?.modified().ok()?;
+		let age = SystemTime::now()
+			.duration_since(modified)
+			.unwrap_or_default();
+		if age > self.ttl {
+			return None;
+		}
+		fs::read_to_string(entry).ok()
+	}
+
+	fn put(&self, url: &str, text: &str) -> io::Result<()> {
+		fs::create_dir_all(&self.dir)?;
+		fs::write(self.entry(url), text)
+	}
+}
+
+/// Returns the license text at `url`, from `cache` if it holds a fresh copy and
+/// otherwise from `fetch`, storing what was fetched in `cache`.
+pub fn fetch_license(
+	url: &str,
+	cache: Option<&LicenseCache>,
+	fetch: impl FnOnce(&str) -> io::Result<String>,
+) -> io::Result<String> {
+	if let Some(text) = cache.and_then(|cache| cache.get(url)) {
+		return Ok(text);
+	}
+	let text = fetch(url)?;
+	if let Some(cache) = cache {
+		cache.put(url, &text)?;
+	}
+	Ok(text)
+}
+
+/// Download `url` over HTTP(S).
+pub fn http_get(url: &str) -> io::Result<String> {
+	let to_io_error =
+		|e: ureq::Error| io::Error::other(format!("Could not fetch license from {}: {}", url, e));
+	ureq::get(url)
+		.call()
+		.map_err(to_io_error)?
+		.body_mut()
+		.read_to_string()
+		.map_err(to_io_error)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::TempDir;
+	use std::cell::Cell;
+
+	#[test]
+	fn a_populated_cache_is_used_instead_of_fetching() {
+		let dir = TempDir::new();
+		let cache = LicenseCache::new(&dir.path().join("licenses"), Duration::from_secs(3600));
+		let fetches = Cell::new(0);
+		let fetch = |url: &str| {
+			fetches.set(fetches.get() + 1);
+			Ok(format!("License from {}", url))
+		};
+		let url = "https://example.com/LICENSE";
+		let first = fetch_license(url, Some(&cache), fetch).unwrap();
+		let second = fetch_license(url, Some(&cache), fetch).unwrap();
+		assert_eq!(first, "License from https://example.com/LICENSE");
+		assert_eq!(second, first);
+		assert_eq!(fetches.get(), 1);
+
+		// Another URL, no cache, or an expired copy all fetch again.
+		fetch_license("https://example.com/COPYING", Some(&cache), fetch).unwrap();
+		fetch_license(url, None, fetch).unwrap();
+		assert_eq!(fetches.get(), 3);
+		std::thread::sleep(Duration::from_millis(20));
+		let expired = LicenseCache::new(&dir.path().join("licenses"), Duration::ZERO);
+		fetch_license(url, Some(&expired), fetch).unwrap();
+		assert_eq!(fetches.get(), 4);
+	}
+}
+
+/*
+ * License:
+ * Copyright (c) 2025 Eric Hernandez
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
@@ -1,15 +1,51 @@
 /* Copyright (c) 2025 Eric Hernandez  */
 
+mod cache;
+mod fetch;
+mod metadata;
+mod plan;
+mod rules;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io;
-use std::path::Path;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 
-use chrono::{Datelike, Utc};
-use clap::{Arg, ArgAction, Command};
+use chrono::{DateTime, Datelike, Utc};
+use clap::builder::PossibleValuesParser;
+use clap::{Arg, ArgAction, ArgGroup, Command};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::WalkBuilder;
+use notify::{RecursiveMode, Watcher};
 use regex::Regex;
-use walkdir::WalkDir;
+use similar::udiff::UnifiedDiffHunk;
+use similar::{DiffOp, TextDiff};
+
+use cache::Cache;
+use rules::Rules;
+
+/// Exit status when check mode found files that would change.
+const EXIT_CHANGES_PENDING: u8 = 1;
+/// Exit status when the run could not be completed.
+const EXIT_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
+	match run() {
+		Ok(code) => code,
+		Err(e) => {
+			eprintln!("Error: {}", e);
+			ExitCode::from(EXIT_ERROR)
+		}
+	}
+}
 
-fn main() -> io::Result<()> {
+fn run() -> io::Result<ExitCode> {
 	let matches = Command::new("copywriter")
 		.version("1.0")
 		.author("Your Name")
@@ -19,15 +55,41 @@ fn main() -> io::Result<()> {
 				.short('a')
 				.long("author")
 				.value_name("NAME")
-				.help("Sets the copyright author name")
-				.required(true),
+				.help("Sets the copyright author name (default: $COPYWRITER_AUTHOR, then git's user.name)"),
+		)
+		.arg(
+			Arg::new("author-env")
+				.long("author-env")
+				.value_name("VAR")
+				.default_value("COPYWRITER_AUTHOR")
+				.help("Environment variable to read the author from when --author is not given"),
 		)
 		.arg(
 			Arg::new("path")
 				.help("File or directory to process")
-				.required(true)
+				.required_unless_present("stdin")
 				.index(1),
 		)
+		.arg(
+			Arg::new("stdin")
+				.long("stdin")
+				.help("Read a single file's content from stdin and write the result to stdout")
+				.action(ArgAction::SetTrue)
+				.conflicts_with_all(["check", "dry-run", "watch", "list-changed", "preview"]),
+		)
+		.arg(
+			Arg::new("stdin-path")
+				.long("stdin-path")
+				.value_name("PATH")
+				.help("With --stdin, the file name whose extension selects the comment style")
+				.requires("stdin"),
+		)
+		.arg(
+			Arg::new("git-root")
+				.long("git-root")
+				.value_name("DIR")
+				.help("Run git commands in the repository at DIR instead of the processed path"),
+		)
 		.arg(
 			Arg::new("license")
 				.short('l')
@@ -35,237 +97,4810 @@ fn main() -> io::Result<()> {
 				.value_name("FILE")
 				.help("Path to license file (default: searches for LICENSE in project root)"),
 		)
+		.arg(
+			Arg::new("relicense")
+				.long("relicense")
+				.value_name("FILE")
+				.help("Replace the license footer of every file with the text of the new license FILE, keeping the copyright headers")
+				.conflicts_with_all(["license", "license-url"]),
+		)
+		.arg(
+			Arg::new("spdx-id")
+				.long("spdx-id")
+				.value_name("ID")
+				.help("With --relicense, also rewrite SPDX-License-Identifier tags to ID")
+				.requires("relicense"),
+		)
+		.arg(
+			Arg::new("license-url")
+				.long("license-url")
+				.value_name("URL")
+				.help("Fetch the license text from URL instead of a local file")
+				.conflicts_with("license"),
+		)
+		.arg(
+			Arg::new("license-cache-dir")
+				.long("license-cache-dir")
+				.value_name("DIR")
+				.help("Cache license text fetched with --license-url in DIR")
+				.requires("license-url"),
+		)
+		.arg(
+			Arg::new("license-cache-ttl")
+				.long("license-cache-ttl")
+				.value_name("SECONDS")
+				.help("How long a cached license is reused before it is fetched again")
+				.value_parser(clap::value_parser!(u64))
+				.default_value("86400"),
+		)
+		.arg(
+			Arg::new("no-cache")
+				.long("no-cache")
+				.help("Fetch the license even if --license-cache-dir holds a copy")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("license-names")
+				.long("license-names")
+				.value_name("LIST")
+				.value_delimiter(',')
+				.help("Comma-separated license filenames to search for (default: LICENSE,LICENSE.md,LICENSE.txt)"),
+		)
+		.arg(
+			Arg::new("footer-once-per-directory")
+				.long("footer-once-per-directory")
+				.help("Write the license footer into only one file per directory; every file still gets a header")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("footer-file")
+				.long("footer-file")
+				.value_name("NAME")
+				.help("With --footer-once-per-directory, the file name that carries the footer where present")
+				.requires("footer-once-per-directory"),
+		)
+		.arg(
+			Arg::new("copyright-word")
+				.long("copyright-word")
+				.value_name("FORM")
+				.help("Write notices as 'Copyright' (spelled), '(c)' (symbol) or 'Copyright (c)' (both), converting existing ones")
+				.value_parser(PossibleValuesParser::new(
+					COPYRIGHT_WORDS.iter().map(|(name, _)| *name),
+				)),
+		)
+		.arg(
+			Arg::new("holder-order")
+				.long("holder-order")
+				.value_name("ORDER")
+				.help("Order of the copyright lines in headers with several holders: as written (config), by holder name (alpha), or by first year (year)")
+				.value_parser(HOLDER_ORDERS)
+				.default_value("config"),
+		)
+		.arg(
+			Arg::new("header-trailer")
+				.long("header-trailer")
+				.value_name("STR")
+				.help("Add a comment line with STR directly after the copyright header, e.g. a rule")
+				.allow_hyphen_values(true),
+		)
+		.arg(
+			Arg::new("require-eof-comment")
+				.long("require-eof-comment")
+				.value_name("STR")
+				.help("Require every file to end with the line STR, e.g. '// EOF', adding it after the footer where missing")
+				.allow_hyphen_values(true),
+		)
+		.arg(
+			Arg::new("indent")
+				.long("indent")
+				.value_name("N")
+				.help("Indent every header and footer line by N characters")
+				.value_parser(clap::value_parser!(usize)),
+		)
+		.arg(
+			Arg::new("indent-char")
+				.long("indent-char")
+				.value_name("CHAR")
+				.help("Character used for --indent")
+				.value_parser(["space", "tab"])
+				.default_value("space"),
+		)
+		.arg(
+			Arg::new("footer-section")
+				.long("footer-section")
+				.value_name("LABEL=FILE")
+				.help("Add a footer section headed LABEL after the license, filled from the template FILE; may be repeated")
+				.action(ArgAction::Append),
+		)
+		.arg(
+			Arg::new("exclude")
+				.long("exclude")
+				.value_name("GLOB")
+				.help("Skip paths matching GLOB (gitignore syntax; a matching directory is skipped entirely); may be repeated")
+				.action(ArgAction::Append),
+		)
+		.arg(
+			Arg::new("include")
+				.long("include")
+				.value_name("GLOB")
+				.help("Only process files matching GLOB (gitignore syntax); may be repeated. --exclude takes precedence")
+				.action(ArgAction::Append),
+		)
+		.arg(
+			Arg::new("no-ignore")
+				.long("no-ignore")
+				.help("Walk into paths listed in .gitignore and .ignore files too")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("lang")
+				.long("lang")
+				.value_name("NAME")
+				.help("Only process files of this language (repeatable)")
+				.value_parser(PossibleValuesParser::new(LANGUAGES.iter().map(|(name, _)| *name)))
+				.action(ArgAction::Append),
+		)
+		.arg(
+			Arg::new("footer-max-lines")
+				.long("footer-max-lines")
+				.value_name("N")
+				.help("Include only the first N lines of the license in footers, plus a note referring to the license file")
+				.value_parser(clap::value_parser!(usize)),
+		)
+		.arg(
+			Arg::new("normalize-whitespace-in-license")
+				.long("normalize-whitespace-in-license")
+				.help("Normalize line endings and spacing in the license text before writing it into footers")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("license-wrap")
+				.long("license-wrap")
+				.value_name("WIDTH")
+				.help("With --normalize-whitespace-in-license, also rewrap license paragraphs to WIDTH columns")
+				.value_parser(clap::value_parser!(usize))
+				.requires("normalize-whitespace-in-license"),
+		)
+		.arg(
+			Arg::new("hcl-comment")
+				.long("hcl-comment")
+				.value_name("MARKER")
+				.help("Comment marker for Terraform and HCL files")
+				.value_parser(["#", "//"])
+				.default_value("#"),
+		)
+		.arg(
+			Arg::new("structured-metadata")
+				.long("structured-metadata")
+				.help("Also process YAML and TOML files, recording the copyright as a top-level copyright key or table")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("rules")
+				.long("rules")
+				.value_name("FILE")
+				.help("Read per-file-type rules (comment style, footer, format) from the JSON FILE"),
+		)
+		.arg(
+			Arg::new("lang-defs")
+				.long("lang-defs")
+				.value_name("DIR")
+				.help("Load custom language definitions (extensions, file names, comment style, interpreters) from the TOML files in DIR"),
+		)
+		.arg(
+			Arg::new("two-pass")
+				.long("two-pass")
+				.help("Stage every update first and rename them into place only after all files were processed")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("verbose")
+				.short('v')
+				.long("verbose")
+				.help("Print the comment style chosen for each file and why")
+				.action(ArgAction::SetTrue),
+		)
 		.arg(
 			Arg::new("dry-run")
 				.long("dry-run")
 				.help("Show what would be done without making changes")
 				.action(ArgAction::SetTrue),
 		)
+		.arg(
+			Arg::new("plan")
+				.long("plan")
+				.value_name("FILE")
+				.help("With --dry-run, save the numbered list of files that would change to FILE; with --apply-selected, read it"),
+		)
+		.arg(
+			Arg::new("apply-selected")
+				.long("apply-selected")
+				.value_name("RANGES")
+				.help("Apply only these entries of the --plan saved by a dry run, e.g. 1,3-5")
+				.requires("plan")
+				.conflicts_with_all(["dry-run", "check"]),
+		)
+		.arg(
+			Arg::new("check")
+				.long("check")
+				.visible_alias("fail-on-change")
+				.help("Report files that are missing an up-to-date header or footer and exit with status 1, without making changes")
+				.action(ArgAction::SetTrue)
+				.conflicts_with("dry-run"),
+		)
+		.arg(
+			Arg::new("paths")
+				.long("paths")
+				.value_name("GLOBS")
+				.help("Only check files matching these comma-separated globs, relative to the path")
+				.value_delimiter(',')
+				.action(ArgAction::Append)
+				.requires("check"),
+		)
+		.arg(
+			Arg::new("check-only-extensions")
+				.long("check-only-extensions")
+				.value_name("LIST")
+				.help("Only check files with these comma-separated extensions, e.g. rs,go")
+				.value_delimiter(',')
+				.action(ArgAction::Append)
+				.requires("check"),
+		)
+		.arg(
+			Arg::new("format")
+				.long("format")
+				.value_name("FORMAT")
+				.help("Console output format; junit emits a JUnit XML report of the check")
+				.value_parser(OUTPUT_FORMATS)
+				.default_value("text")
+				.requires_if("junit", "check"),
+		)
+		.arg(
+			Arg::new("summary-json")
+				.long("summary-json")
+				.value_name("FILE")
+				.help("Write the aggregate counts of the run as JSON to FILE, whatever the console format"),
+		)
+		.arg(
+			Arg::new("skipped-report")
+				.long("skipped-report")
+				.value_name("FILE")
+				.help("Write every skipped file and the reason it was skipped to FILE"),
+		)
+		.arg(
+			Arg::new("header-regex")
+				.long("header-regex")
+				.value_name("PATTERN")
+				.help("In check mode, also require each file's top block to match PATTERN ({year} and {author} are expanded)")
+				.requires("check"),
+		)
+		.arg(
+			Arg::new("verify-no-duplicate-headers")
+				.long("verify-no-duplicate-headers")
+				.help("With --check, fail files that carry more than one copyright notice for the same holder, in one-line headers or block comments")
+				.action(ArgAction::SetTrue)
+				.requires("check"),
+		)
+		.arg(
+			Arg::new("min-file-size")
+				.long("min-file-size")
+				.value_name("BYTES")
+				.help("Skip files smaller than BYTES, such as empty stub modules")
+				.value_parser(clap::value_parser!(u64))
+				.default_value("0"),
+		)
+		.arg(
+			Arg::new("header-within-lines")
+				.long("header-within-lines")
+				.value_name("N")
+				.help("With --check, fail files whose copyright header is missing or starts after line N")
+				.value_parser(clap::value_parser!(usize))
+				.requires("check"),
+		)
+		.arg(
+			Arg::new("timestamp")
+				.long("timestamp")
+				.help("Add a 'Last updated: <date>' line to the license footer")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("preserve-gaps")
+				.long("preserve-gaps")
+				.help("Keep gaps in year ranges, e.g. update 2018-2020 to '2018-2020, 2025' rather than 2018-2025")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("year-list-max")
+				.long("year-list-max")
+				.value_name("N")
+				.help("With --preserve-gaps, collapse year lists of more than N entries into one range")
+				.value_parser(clap::value_parser!(usize))
+				.requires("preserve-gaps"),
+		)
+		.arg(
+			Arg::new("normalize-prefix")
+				.long("normalize-prefix")
+				.help("Rewrite existing headers to use the file type's canonical comment delimiters and spacing")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("normalize-punctuation")
+				.long("normalize-punctuation")
+				.help("Rewrite notice variants such as 'Copyright(c) 2020 Name' and 'Copyright © 2020, Name' to the canonical form")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("keep-existing-style")
+				.long("keep-existing-style")
+				.help("Keep the comment delimiters of an existing header instead of the file type's default")
+				.action(ArgAction::SetTrue)
+				.conflicts_with("normalize-prefix"),
+		)
+		.arg(
+			Arg::new("merge-into-existing-license-block")
+				.long("merge-into-existing-license-block")
+				.help("In files that already have a license comment block, put the copyright line into that block instead of adding a header and footer")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("repair-footer")
+				.long("repair-footer")
+				.help("Move license footers that are followed by code to the end of the file")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("cache")
+				.long("cache")
+				.value_name("FILE")
+				.help("Remember facts about this run (such as the license hash) in FILE for later runs"),
+		)
+		.arg(
+			Arg::new("time-budget")
+				.long("time-budget")
+				.value_name("DURATION")
+				.help("Stop starting new files once DURATION (e.g. 90s, 5m) has passed; with --cache, the next run continues where this one stopped")
+				.value_parser(parse_duration),
+		)
+		.arg(
+			Arg::new("incremental")
+				.long("incremental")
+				.help("Only process files modified since the last run recorded in --cache, if it was made with the same settings")
+				.action(ArgAction::SetTrue)
+				.requires("cache"),
+		)
+		.arg(
+			Arg::new("check-stale-footers")
+				.long("check-stale-footers")
+				.help("If the license changed since the run recorded in --cache, report files with stale footers and exit nonzero")
+				.action(ArgAction::SetTrue)
+				.requires("cache")
+				.conflicts_with("dry-run"),
+		)
+		.arg(
+			Arg::new("watch")
+				.long("watch")
+				.help("After processing, keep watching the path and update source files as they change")
+				.action(ArgAction::SetTrue)
+				.conflicts_with("check"),
+		)
+		.arg(
+			Arg::new("diff")
+				.long("diff")
+				.help("In dry-run mode, show a unified diff of the changes")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("diff-scope")
+				.long("diff-scope")
+				.value_name("SCOPE")
+				.help("Restrict dry-run diffs to the header or footer changes")
+				.value_parser(DIFF_SCOPES)
+				.default_value("both"),
+		)
+		.arg(
+			Arg::new("insertion-context")
+				.long("insertion-context")
+				.value_name("N")
+				.help("Show N extra lines of diff context around inserted lines")
+				.value_parser(clap::value_parser!(usize))
+				.requires("diff"),
+		)
+		.arg(
+			Arg::new("list-changed")
+				.long("list-changed")
+				.help("Print only the paths of files that would change, one per line, without making changes")
+				.action(ArgAction::SetTrue)
+				.conflicts_with_all(["check", "watch"]),
+		)
+		.arg(
+			Arg::new("preview")
+				.long("preview")
+				.value_name("N")
+				.help("Dry-run the first N files with diffs, then stop")
+				.value_parser(clap::value_parser!(usize))
+				.conflicts_with("check"),
+		)
+		.arg(
+			Arg::new("deterministic")
+				.long("deterministic")
+				.help("Make the run reproducible: take the year from SOURCE_DATE_EPOCH and process files in sorted order")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("strip-bom")
+				.long("strip-bom")
+				.help("Only remove UTF-8 byte order marks from the files, reporting which had one")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("report-holders")
+				.long("report-holders")
+				.help("Report each copyright holder's latest year, flagging stale holders, without modifying files")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("report-oldest-files")
+				.long("report-oldest-files")
+				.help("List files by the last year of their copyright notices, oldest first, without modifying files")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("compare-two-authors")
+				.long("compare-two-authors")
+				.value_names(["A", "B"])
+				.num_args(2)
+				.help("Classify each file as held by author A, author B, both, or neither, and summarize, without modifying files"),
+		)
+		.arg(
+			Arg::new("scan-duplicate-headers")
+				.long("scan-duplicate-headers")
+				.help("Collapse repeated copyright headers for the same holder into one, crediting all their years; with --dry-run, only report them")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("report-licenses")
+				.long("report-licenses")
+				.help("Report the license file that applies to each directory with source files, or that it is missing, without modifying files")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("audit-json")
+				.long("audit-json")
+				.value_name("FILE")
+				.help("Write each file's header and footer presence, copyright holders and years, declared licenses, license file, and skip reason as JSON to FILE, without modifying files"),
+		)
+		.group(
+			ArgGroup::new("license-lookups")
+				.args(["report-licenses", "audit-json"])
+				.multiple(true),
+		)
+		.arg(
+			Arg::new("parallel-license-resolution")
+				.long("parallel-license-resolution")
+				.help("With --report-licenses or --audit-json, look up each directory's license file on several threads sharing one cache")
+				.action(ArgAction::SetTrue)
+				.requires("license-lookups"),
+		)
+		.arg(
+			Arg::new("scan-encoding")
+				.long("scan-encoding")
+				.help("Report each file's detected encoding, flagging files that are not valid UTF-8, without modifying files")
+				.action(ArgAction::SetTrue),
+		)
+		.arg(
+			Arg::new("allowed-licenses")
+				.long("allowed-licenses")
+				.value_name("LIST")
+				.help("Report files that declare a license outside this comma-separated list of SPDX identifiers and exit with status 1, without modifying files")
+				.value_delimiter(',')
+				.action(ArgAction::Append)
+				.conflicts_with("dry-run"),
+		)
+		.arg(
+			Arg::new("report-format")
+				.long("report-format")
+				.value_name("FORMAT")
+				.help("Output format for --report-holders")
+				.value_parser(REPORT_FORMATS)
+				.default_value("text")
+				.requires("report-holders"),
+		)
 		.get_matches();
 
-	let author_name = matches
-		.get_one::<String>("author")
-		.expect("author is required");
-	let path_str = matches.get_one::<String>("path").expect("path is required");
-	let dry_run = matches.get_flag("dry-run");
-
-	// Determine license content.
-	let license_content = if let Some(license_path) = matches.get_one::<String>("license") {
-		fs::read_to_string(license_path)?
-	} else {
-		find_and_read_license(path_str)?
+	// With --stdin, the path only serves to locate the license and git repository.
+	let path_str = matches
+		.get_one::<String>("path")
+		.map_or(".", String::as_str);
+	let stdin = matches.get_flag("stdin");
+	let preview = matches.get_one::<usize>("preview").copied();
+	let list_changed = matches.get_flag("list-changed");
+	let dry_run = matches.get_flag("dry-run") || preview.is_some() || list_changed;
+	let deterministic = matches.get_flag("deterministic");
+	let structured_metadata = matches.get_flag("structured-metadata");
+	let mut rules = match matches.get_one::<String>("rules") {
+		Some(rules_path) => Rules::load(Path::new(rules_path), COMMENT_STYLES)?,
+		None => Rules::default(),
+	};
+	if let Some(dir) = matches.get_one::<String>("lang-defs") {
+		rules.extend(Rules::load_lang_defs(Path::new(dir), COMMENT_STYLES)?);
+	}
+	let mut walk_options = WalkOptions {
+		sorted: deterministic,
+		extensions: matches.get_many::<String>("lang").map(|langs| {
+			langs
+				.flat_map(|lang| language_extensions(lang).iter().copied())
+				.collect()
+		}),
+		metadata_files: structured_metadata,
+		rules: rules.clone(),
+		paths: matches
+			.get_many::<String>("paths")
+			.map(|globs| build_glob_set(globs.map(String::as_str)))
+			.transpose()?,
+		check_extensions: matches
+			.get_many::<String>("check-only-extensions")
+			.map(|extensions| {
+				extensions
+					.map(|ext| ext.trim_start_matches('.').to_lowercase())
+					.collect()
+			}),
+		ignore_files: !matches.get_flag("no-ignore"),
+		overrides: None,
 	};
 
 	let path = Path::new(path_str);
-	if path.is_file() {
-		update_file(path, author_name, &license_content, dry_run)?;
-	} else if path.is_dir() {
-		for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
-			let entry_path = entry.path();
-			if entry_path.is_file() && is_source_file(entry_path) {
-				update_file(entry_path, author_name, &license_content, dry_run)?;
-			}
-		}
-	} else {
+	if !path.is_file() && !path.is_dir() {
 		eprintln!(
 			"Path does not exist or is not accessible: {}",
 			path.display()
 		);
+		return Ok(ExitCode::from(EXIT_ERROR));
 	}
+	walk_options.overrides = build_overrides(
+		path,
+		matches.get_many::<String>("include").into_iter().flatten(),
+		matches.get_many::<String>("exclude").into_iter().flatten(),
+	)?;
 
-	Ok(())
-}
+	if matches.get_flag("strip-bom") {
+		strip_boms(&collect_source_files(path, &walk_options), dry_run)?;
+		return Ok(ExitCode::SUCCESS);
+	}
 
-/// Search for a LICENSE file in the current or parent directories.
-fn find_and_read_license(start_path: &str) -> io::Result<String> {
-	let start_path = Path::new(start_path);
-	let mut current_dir = if start_path.is_file() {
-		start_path.parent().unwrap_or(Path::new(".")).to_path_buf()
-	} else {
-		start_path.to_path_buf()
-	};
+	if matches.get_flag("report-holders") {
+		let now = reference_time(deterministic)?;
+		let latest_years = aggregate_holders(&collect_source_files(path, &walk_options));
+		let format = matches
+			.get_one::<String>("report-format")
+			.expect("report-format has a default");
+		print!(
+			"{}",
+			render_holders_report(&latest_years, now.year(), format)
+		);
+		return Ok(ExitCode::SUCCESS);
+	}
 
-	for _ in 0..100 {
-		for license_filename in &["LICENSE", "LICENSE.md", "LICENSE.txt"] {
-			let license_path = current_dir.join(license_filename);
-			if license_path.exists() {
-				return fs::read_to_string(license_path);
-			}
-		}
-		if let Some(parent) = current_dir.parent() {
-			current_dir = parent.to_path_buf();
-		} else {
-			break;
+	if matches.get_flag("report-oldest-files") {
+		for (last_year, file) in oldest_files(&collect_source_files(path, &walk_options)) {
+			println!("{}: {}", last_year, file.display());
 		}
+		return Ok(ExitCode::SUCCESS);
 	}
 
-	Err(io::Error::new(
-		io::ErrorKind::NotFound,
-		"License file not found",
-	))
-}
+	if let Some(authors) = matches.get_many::<String>("compare-two-authors") {
+		let authors: Vec<&str> = authors.map(String::as_str).collect();
+		let (a, b) = (authors[0], authors[1]);
+		let mut counts = [0; 4];
+		for file in collect_source_files(path, &walk_options) {
+			let Ok(content) = fs::read_to_string(&file) else {
+				continue;
+			};
+			let notices = file_notices(&file, &content);
+			let held_by = |author| {
+				notices
+					.iter()
+					.any(|notice| holder_is(&notice.holder, author))
+			};
+			let (class, held) = match (held_by(a), held_by(b)) {
+				(true, false) => (0, a),
+				(false, true) => (1, b),
+				(true, true) => (2, "both"),
+				(false, false) => (3, "neither"),
+			};
+			counts[class] += 1;
+			println!("{}: {}", file.display(), held);
+		}
+		println!();
+		for (held, count) in [a, b, "both", "neither"].iter().zip(counts) {
+			println!("Held by {}: {}", held, count);
+		}
+		return Ok(ExitCode::SUCCESS);
+	}
 
-/// Check whether a file is a source file based on its extension.
-fn is_source_file(path: &Path) -> bool {
-	let source_extensions = [
-		".rs", ".py", ".js", ".jsx", ".ts", ".tsx", ".c", ".cpp", ".h", ".hpp", ".java", ".go",
-		".rb", ".php", ".swift", ".kt", ".cs", ".sh", ".bash", ".pl", ".pm", ".lua", ".scala",
-		".css", ".scss", ".html", ".xml", ".json",
-	];
+	let license_names: Vec<&str> = match matches.get_many::<String>("license-names") {
+		Some(names) => names.map(String::as_str).collect(),
+		None => DEFAULT_LICENSE_NAMES.to_vec(),
+	};
 
-	if let Some(ext) = path.extension() {
-		let ext = format!(".{}", ext.to_string_lossy().to_lowercase());
-		source_extensions.contains(&ext.as_str())
-	} else {
-		false
+	if matches.get_flag("scan-duplicate-headers") {
+		for file in collect_source_files(path, &walk_options) {
+			let Ok(content) = fs::read_to_string(&file) else {
+				continue;
+			};
+			let (comment_start, _, comment_end) = get_comment_style(&file);
+			let Some(collapsed) = collapse_duplicate_headers(&content, comment_start, comment_end)
+			else {
+				continue;
+			};
+			if dry_run {
+				println!("Duplicate headers: {}", file.display());
+			} else {
+				fs::write(&file, collapsed)?;
+				println!("Collapsed duplicate headers: {}", file.display());
+			}
+		}
+		return Ok(ExitCode::SUCCESS);
 	}
-}
 
-/// Returns a tuple of (block comment start, comment prefix, block comment end) for a file.
-fn get_comment_style(path: &Path) -> (&'static str, &'static str, &'static str) {
-	if let Some(ext) = path.extension() {
-		match ext.to_string_lossy().to_lowercase().as_str() {
-			// C-style comments.
-			"rs" | "c" | "cpp" | "h" | "hpp" | "js" | "jsx" | "ts" | "tsx" | "go" | "java"
-			| "swift" | "kt" | "scala" | "css" | "scss" | "cs" => ("/*", " * ", " */"),
-			// Hash-style comments.
-			"py" | "rb" | "sh" | "bash" | "pl" | "pm" | "php" => ("#", "# ", "#"),
-			// Lua-style comments.
-			"lua" => ("--[[", "-- ", "--]]"),
-			// HTML/XML-style comments.
-			"html" | "xml" => ("<!--", " ", "-->"),
-			_ => ("/*", " * ", " */"),
+	let parallel_license_resolution = matches.get_flag("parallel-license-resolution");
+	if matches.get_flag("report-licenses") {
+		let dirs: BTreeSet<PathBuf> = collect_source_files(path, &walk_options)
+			.iter()
+			.filter_map(|file| file.parent().map(Path::to_path_buf))
+			.collect();
+		let lookup = LicenseLookup::new(&license_names);
+		if parallel_license_resolution {
+			lookup.resolve_parallel(&dirs.iter().cloned().collect::<Vec<_>>());
 		}
-	} else {
-		("#", "# ", "#")
+		for dir in dirs {
+			match lookup.find(&dir) {
+				Some(license_path) => println!("{}: {}", dir.display(), license_path.display()),
+				None => println!("{}: missing", dir.display()),
+			}
+		}
+		return Ok(ExitCode::SUCCESS);
 	}
-}
 
-/// Update a single file with the copyright header at the top and license footer
-/// at the bottom.
-fn update_file(
-	file_path: &Path,
-	author_name: &str,
-	license_content: &str,
-	dry_run: bool,
-) -> io::Result<()> {
-	// Skip very large files.
-	let metadata = fs::metadata(file_path)?;
-	if metadata.len() > 1_000_000 {
-		println!("Skipping large file: {}", file_path.display());
-		return Ok(());
+	if let Some(audit_path) = matches.get_one::<String>("audit-json") {
+		let min_file_size = *matches
+			.get_one::<u64>("min-file-size")
+			.expect("min-file-size has a default");
+		// An earlier audit in the tree is JSON, but not one of the sources.
+		let mut files = collect_source_files(path, &walk_options);
+		if let Ok(audit_file) = fs::canonicalize(audit_path) {
+			files.retain(|file| fs::canonicalize(file).ok().as_ref() != Some(&audit_file));
+		}
+		files.sort();
+		let lookup = LicenseLookup::new(&license_names);
+		if parallel_license_resolution {
+			let dirs: BTreeSet<PathBuf> = files
+				.iter()
+				.filter_map(|file| file.parent().map(Path::to_path_buf))
+				.collect();
+			lookup.resolve_parallel(&dirs.into_iter().collect::<Vec<_>>());
+		}
+		let audit = render_audit_json(&files, &lookup, min_file_size)?;
+		fs::write(audit_path, audit)?;
+		return Ok(ExitCode::SUCCESS);
 	}
 
-	// Read the file as text.
-	let content = match fs::read_to_string(file_path) {
-		Ok(c) => c,
-		Err(_) => {
-			println!("Skipping binary file: {}", file_path.display());
-			return Ok(());
+	if matches.get_flag("scan-encoding") {
+		let mut not_utf8 = 0;
+		for file in collect_source_files(path, &walk_options) {
+			let bytes = match fs::read(&file) {
+				Ok(bytes) => bytes,
+				Err(e) => {
+					eprintln!("Failed to read {}: {}", file.display(), e);
+					continue;
+				}
+			};
+			let encoding = detect_encoding(&bytes);
+			if encoding == "UTF-8" {
+				println!("{}: UTF-8", file.display());
+			} else {
+				println!("{}: {} (not UTF-8)", file.display(), encoding);
+				not_utf8 += 1;
+			}
 		}
-	};
-
-	let (comment_start, comment_prefix, comment_end) = get_comment_style(file_path);
-	let current_year = Utc::now().year();
+		if not_utf8 > 0 {
+			eprintln!("{} file(s) are not valid UTF-8", not_utf8);
+		}
+		return Ok(ExitCode::SUCCESS);
+	}
+
+	if let Some(allowed) = matches.get_many::<String>("allowed-licenses") {
+		let allowed: Vec<&str> = allowed.map(String::as_str).collect();
+		let mut offenders = 0;
+		for file in collect_source_files(path, &walk_options) {
+			let Ok(content) = fs::read_to_string(&file) else {
+				continue;
+			};
+			let disallowed: Vec<_> = declared_licenses(&content)
+				.into_iter()
+				.filter(|license| !allowed.iter().any(|a| a.eq_ignore_ascii_case(license)))
+				.collect();
+			if !disallowed.is_empty() {
+				println!(
+					"Disallowed license {}: {}",
+					disallowed.join(", "),
+					file.display()
+				);
+				offenders += 1;
+			}
+		}
+		if offenders > 0 {
+			eprintln!(
+				"{} file(s) declare a license that is not allowed",
+				offenders
+			);
+			return Ok(ExitCode::from(EXIT_CHANGES_PENDING));
+		}
+		return Ok(ExitCode::SUCCESS);
+	}
+
+	let author_env = matches
+		.get_one::<String>("author-env")
+		.expect("author-env has a default");
+	let git_root = match matches.get_one::<String>("git-root") {
+		Some(git_root) => {
+			let git_root = PathBuf::from(git_root);
+			if !is_git_repo(&git_root) {
+				eprintln!("Not a git repository: {}", git_root.display());
+				return Ok(ExitCode::from(EXIT_ERROR));
+			}
+			git_root
+		}
+		None => default_git_root(path),
+	};
+	let config_path = find_config(path);
+	let Some(author_name) = resolve_author(
+		matches.get_one::<String>("author"),
+		author_env,
+		config_path.as_deref(),
+		&git_root,
+	)?
+	else {
+		eprintln!(
+			"No author given: pass --author, set {}, set author in {}, or configure git's user.name",
+			author_env, CONFIG_FILE_NAME
+		);
+		return Ok(ExitCode::from(EXIT_ERROR));
+	};
+	let author_name = &author_name;
+	// Determine license content.
+	let (license_path, mut license_content) = if let Some(license_path) = matches
+		.get_one::<String>("license")
+		.or(matches.get_one::<String>("relicense"))
+	{
+		(
+			PathBuf::from(license_path),
+			fs::read_to_string(license_path)?,
+		)
+	} else if let Some(url) = matches.get_one::<String>("license-url") {
+		let license_cache = matches
+			.get_one::<String>("license-cache-dir")
+			.filter(|_| !matches.get_flag("no-cache"))
+			.map(|dir| {
+				let ttl = matches
+					.get_one::<u64>("license-cache-ttl")
+					.expect("license-cache-ttl has a default");
+				fetch::LicenseCache::new(Path::new(dir), Duration::from_secs(*ttl))
+			});
+		(
+			PathBuf::from(url),
+			fetch::fetch_license(url, license_cache.as_ref(), fetch::http_get)?,
+		)
+	} else {
+		find_and_read_license(path_str, &license_names)?
+	};
+	if matches.get_flag("normalize-whitespace-in-license") {
+		license_content = normalize_license(
+			&license_content,
+			matches.get_one::<usize>("license-wrap").copied(),
+		);
+	}
+	if let Some(max_lines) = matches.get_one::<usize>("footer-max-lines") {
+		let license_name = license_path
+			.file_name()
+			.map_or("LICENSE".into(), |name| name.to_string_lossy());
+		license_content = truncate_license(&license_content, *max_lines, &license_name);
+	}
+
+	let cache_path = matches.get_one::<String>("cache").map(PathBuf::from);
+	let mut cache = match &cache_path {
+		Some(cache_path) => Cache::load(cache_path)?,
+		None => Cache::default(),
+	};
+	let license_hash = cache::hash(&license_content);
+	let stale_footers = matches.get_flag("check-stale-footers");
+	if stale_footers && cache.license_hash.as_ref() == Some(&license_hash) {
+		println!("License unchanged since the last recorded run");
+		return Ok(ExitCode::SUCCESS);
+	}
+
+	let now = reference_time(deterministic)?;
+	let header_regex = match matches.get_one::<String>("header-regex") {
+		Some(pattern) => Some(expand_header_regex(pattern, author_name, now.year())?),
+		None => None,
+	};
+
+	let format = matches
+		.get_one::<String>("format")
+		.expect("format has a default");
+	let mut footer_sections = Vec::new();
+	for section in matches
+		.get_many::<String>("footer-section")
+		.into_iter()
+		.flatten()
+	{
+		let Some((label, file)) = section
+			.split_once('=')
+			.filter(|(label, _)| !label.is_empty() && *label != "License")
+		else {
+			eprintln!("Invalid footer section, expected LABEL=FILE: {}", section);
+			return Ok(ExitCode::from(EXIT_ERROR));
+		};
+		footer_sections.push((
+			label.to_string(),
+			fs::read_to_string(file)?.trim_end().to_string(),
+		));
+	}
+
+	let mut options = Options {
+		author: author_name.clone(),
+		license: license_content,
+		now,
+		dry_run,
+		diff: matches.get_flag("diff") || preview.is_some(),
+		diff_scope: matches
+			.get_one::<String>("diff-scope")
+			.expect("diff-scope has a default")
+			.clone(),
+		insertion_context: matches
+			.get_one::<usize>("insertion-context")
+			.copied()
+			.unwrap_or(0),
+		check: matches.get_flag("check") || stale_footers,
+		stale_footers,
+		header_regex,
+		spdx_id: matches.get_one::<String>("spdx-id").cloned(),
+		no_duplicate_headers: matches.get_flag("verify-no-duplicate-headers"),
+		header_within_lines: matches.get_one::<usize>("header-within-lines").copied(),
+		min_file_size: *matches
+			.get_one::<u64>("min-file-size")
+			.expect("min-file-size has a default"),
+		repair_footer: matches.get_flag("repair-footer"),
+		merge_license_block: matches.get_flag("merge-into-existing-license-block"),
+		timestamp: matches.get_flag("timestamp"),
+		normalize_prefix: matches.get_flag("normalize-prefix"),
+		normalize_punctuation: matches.get_flag("normalize-punctuation"),
+		keep_existing_style: matches.get_flag("keep-existing-style"),
+		preserve_gaps: matches.get_flag("preserve-gaps"),
+		year_list_max: matches.get_one::<usize>("year-list-max").copied(),
+		hcl_slash_comments: matches.get_one::<String>("hcl-comment").map(String::as_str)
+			== Some("//"),
+		structured_metadata,
+		rules,
+		eof_comment: matches.get_one::<String>("require-eof-comment").cloned(),
+		header_trailer: matches.get_one::<String>("header-trailer").cloned(),
+		holder_order: matches
+			.get_one::<String>("holder-order")
+			.expect("holder-order has a default")
+			.clone(),
+		copyright_word: matches
+			.get_one::<String>("copyright-word")
+			.and_then(|form| COPYRIGHT_WORDS.iter().find(|(name, _)| name == form))
+			.map(|(_, word)| *word),
+		indent: {
+			let indent_char = match matches.get_one::<String>("indent-char").map(String::as_str) {
+				Some("tab") => "\t",
+				_ => " ",
+			};
+			indent_char.repeat(matches.get_one::<usize>("indent").copied().unwrap_or(0))
+		},
+		footer_sections,
+		footer_files: None,
+		staged: matches.get_flag("two-pass").then(RefCell::default),
+		format: format.clone(),
+		verbose: matches.get_flag("verbose"),
+		quiet: format != "text" || list_changed || stdin,
+	};
+
+	if stdin {
+		let mut content = String::new();
+		io::stdin().read_to_string(&mut content)?;
+		let stdin_path = Path::new(
+			matches
+				.get_one::<String>("stdin-path")
+				.map_or("stdin", String::as_str),
+		);
+		let final_content = annotate(stdin_path, &content, &options)
+			.map_or(content, |(_, final_content)| final_content);
+		print!("{}", final_content);
+		return Ok(ExitCode::SUCCESS);
+	}
+
+	let plan_path = matches.get_one::<String>("plan").map(Path::new);
+	if plan_path.is_some() && !dry_run && !matches.contains_id("apply-selected") {
+		eprintln!("--plan needs --dry-run to save a plan or --apply-selected to apply one");
+		return Ok(ExitCode::from(EXIT_ERROR));
+	}
+	let mut files = collect_source_files(path, &walk_options);
+	if let (Some(plan_path), Some(ranges)) =
+		(plan_path, matches.get_one::<String>("apply-selected"))
+	{
+		files = match plan::select(&plan::load(plan_path)?, ranges) {
+			Ok(selected) => selected,
+			Err(e) => {
+				eprintln!("{}", e);
+				return Ok(ExitCode::from(EXIT_ERROR));
+			}
+		};
+	}
+	if options.dry_run {
+		for ignored in ignored_paths(path, &walk_options) {
+			options.note(format!("Skipping ignored path: {}", ignored.display()));
+		}
+	}
+	// A run cut short by --time-budget left a list of the files it did not get to, which
+	// a run with the same settings (and so the same path) picks up.
+	let config_hash = settings_hash(&license_hash, &options);
+	let resumed = if cache.remaining_hash.as_ref() == Some(&config_hash) {
+		cache.remaining.take()
+	} else {
+		None
+	};
+	if let Some(resumed) = &resumed {
+		files.retain(|file| resumed.contains(file));
+	}
+	if matches.get_flag("incremental") {
+		match cache
+			.last_run
+			.filter(|_| cache.config_hash.as_ref() == Some(&config_hash))
+		{
+			Some(last_run) => files.retain(|file| {
+				fs::metadata(file)
+					.and_then(|metadata| metadata.modified())
+					.map_or(true, |modified| unix_millis(modified) > last_run)
+			}),
+			None => options.note(
+				"No earlier run with the same settings recorded; processing every file".into(),
+			),
+		}
+	}
+	// The tool's own input and output files may be JSON or otherwise look like sources,
+	// which would get annotated along with the real ones.
+	for own_file in ["rules", "plan", "cache", "summary-json", "skipped-report"]
+		.iter()
+		.filter_map(|id| matches.get_one::<String>(id).map(PathBuf::from))
+		.chain(config_path.clone())
+	{
+		if let Ok(own_file) = fs::canonicalize(own_file) {
+			files.retain(|file| fs::canonicalize(file).ok().as_ref() != Some(&own_file));
+		}
+	}
+	if let Some(count) = preview {
+		files.truncate(count);
+	}
+	if matches.get_flag("footer-once-per-directory") {
+		let footer_file = matches.get_one::<String>("footer-file").map(String::as_str);
+		options.footer_files = Some(footer_files(&files, footer_file));
+	}
+
+	let summary_path = matches.get_one::<String>("summary-json").map(Path::new);
+	let time_budget = matches.get_one::<Duration>("time-budget").copied();
+	let started = Instant::now();
+	let mut results = Vec::new();
+	let mut remaining = Vec::new();
+	for (i, file) in files.iter().enumerate() {
+		// At least one file is processed, so that every run makes progress.
+		if i > 0 && time_budget.is_some_and(|budget| started.elapsed() > budget) {
+			remaining = files[i..].to_vec();
+			break;
+		}
+		match update_file(file, &options) {
+			Ok(outcome) => results.push((file.clone(), outcome)),
+			Err(e) => {
+				if let Some(staged) = &options.staged {
+					discard_staged(&staged.borrow());
+				}
+				// The run stops here, but the summary still records how far it got.
+				if let Some(summary_path) = summary_path {
+					fs::write(summary_path, render_summary_json(&results, Some(file)))?;
+				}
+				return Err(e);
+			}
+		}
+	}
+	if let Some(staged) = options.staged.take() {
+		commit_staged(&staged.into_inner(), &options)?;
+	}
+	if let Some(summary_path) = summary_path {
+		fs::write(summary_path, render_summary_json(&results, None))?;
+	}
+	if let Some(report_path) = matches.get_one::<String>("skipped-report") {
+		let report: String = results
+			.iter()
+			.filter_map(|(file, outcome)| match outcome {
+				Outcome::Skipped(reason) => {
+					Some(format!("{}: {}\n", file.display(), reason.as_str()))
+				}
+				_ => None,
+			})
+			.collect();
+		fs::write(report_path, report)?;
+	}
+
+	if let Some(plan_path) = plan_path.filter(|_| dry_run) {
+		let planned: Vec<&Path> = results
+			.iter()
+			.filter(|(_, outcome)| *outcome == Outcome::Changed)
+			.map(|(file, _)| file.as_path())
+			.collect();
+		plan::save(plan_path, &planned)?;
+		println!(
+			"Saved {} planned change(s) to {}:",
+			planned.len(),
+			plan_path.display()
+		);
+		for (i, file) in planned.iter().enumerate() {
+			println!("  {}: {}", i + 1, file.display());
+		}
+	}
+	if options.format == "junit" {
+		print!("{}", render_junit(&results, &options));
+	}
+	if list_changed {
+		for (file, outcome) in &results {
+			if *outcome == Outcome::Changed {
+				println!("{}", file.display());
+			}
+		}
+	}
+	let failures = results
+		.iter()
+		.filter(|(_, outcome)| check_failure(*outcome, &options).is_some())
+		.count();
+
+	if failures > 0 {
+		eprintln!("{} file(s) failed the check", failures);
+	}
+	// Dry and check runs change nothing, so whatever they left is still to do.
+	let writes = !options.dry_run && !options.check;
+	if writes && (!remaining.is_empty() || resumed.is_some()) {
+		if let Some(cache_path) = &cache_path {
+			// The license hash is only recorded once every file has been processed.
+			cache.remaining = (!remaining.is_empty()).then(|| remaining.clone());
+			cache.remaining_hash = cache.remaining.as_ref().map(|_| config_hash.clone());
+			cache.save(cache_path)?;
+		}
+	}
+	if !remaining.is_empty() {
+		eprintln!(
+			"Time budget exceeded: {} file(s) processed, {} remaining",
+			results.len(),
+			remaining.len()
+		);
+		// A check that did not get to every file has not passed.
+		if options.check {
+			return Ok(ExitCode::from(EXIT_CHANGES_PENDING));
+		}
+		return Ok(ExitCode::SUCCESS);
+	}
+	if failures > 0 {
+		return Ok(ExitCode::from(EXIT_CHANGES_PENDING));
+	}
+
+	if let Some(cache_path) = &cache_path {
+		if writes {
+			cache.license_hash = Some(license_hash);
+			cache.last_run = Some(unix_millis(SystemTime::now()));
+			cache.config_hash = Some(config_hash);
+			cache.save(cache_path)?;
+		}
+	}
+
+	if matches.get_flag("watch") {
+		watch(path, &options, &walk_options)?;
+	}
+	Ok(ExitCode::SUCCESS)
+}
+
+/// A hash of everything that decides what files are written with: the command-line
+/// arguments (other than `--incremental`), the license, the author, the year, and the
+/// footer sections. Only a run with the same hash resumes a run stopped by
+/// `--time-budget`, and `--incremental` only trusts an earlier run with the same hash.
+fn settings_hash(license_hash: &str, options: &Options) -> String {
+	let args: Vec<String> = std::env::args_os()
+		.skip(1)
+		.map(|arg| arg.to_string_lossy().into_owned())
+		.filter(|arg| arg != "--incremental")
+		.collect();
+	let sections: Vec<String> = options
+		.footer_sections
+		.iter()
+		.map(|(label, template)| format!("{}={}", label, template))
+		.collect();
+	cache::hash(&format!(
+		"{}\0{}\0{}\0{}\0{}",
+		args.join("\0"),
+		license_hash,
+		options.author,
+		options.now.year(),
+		sections.join("\0")
+	))
+}
+
+/// Milliseconds since the Unix epoch at `time`, or 0 for times before it.
+fn unix_millis(time: SystemTime) -> u64 {
+	time.duration_since(SystemTime::UNIX_EPOCH)
+		.map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// How long the watcher waits for a burst of file events to settle before acting.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Watch `path` and update every file below it that a walk with `walk_options` would
+/// process as it is created or modified, until the process is interrupted. Events
+/// arriving in quick succession (editors often write a file several times per save)
+/// are collected and each file is handled once.
+fn watch(path: &Path, options: &Options, walk_options: &WalkOptions) -> io::Result<()> {
+	let (tx, rx) = mpsc::channel();
+	let mut watcher = notify::recommended_watcher(tx).map_err(io::Error::other)?;
+	watcher
+		.watch(path, RecursiveMode::Recursive)
+		.map_err(io::Error::other)?;
+	println!("Watching {} for changes...", path.display());
+
+	while let Ok(event) = rx.recv() {
+		let mut changed = BTreeSet::new();
+		let mut pending = Some(event);
+		// Only writes restart the wait: files being read, by us or anyone else, must not
+		// hold the batch back.
+		let mut settled = Instant::now() + WATCH_DEBOUNCE;
+		while let Some(event) = pending {
+			match event {
+				Ok(event) if event.kind.is_create() || event.kind.is_modify() => {
+					changed.extend(event.paths);
+					settled = Instant::now() + WATCH_DEBOUNCE;
+				}
+				Ok(_) => {}
+				Err(e) => eprintln!("Watch error: {}", e),
+			}
+			pending = rx
+				.recv_timeout(settled.saturating_duration_since(Instant::now()))
+				.ok();
+		}
+		if changed.is_empty() {
+			continue;
+		}
+
+		// Our own writes trigger events too; a second pass finds nothing to change.
+		// When watching a single file, every event is about that file.
+		let files = if path.is_file() {
+			vec![path.to_path_buf()]
+		} else {
+			walked_files_among(path, walk_options, &changed)?
+		};
+		for file in files {
+			if let Err(e) = update_file(&file, options) {
+				eprintln!("Failed to update {}: {}", file.display(), e);
+			}
+		}
+	}
+
+	Ok(())
+}
+
+/// Determine the copyright author. An explicit `--author` takes precedence, followed
+/// by the environment variable `env_var`, followed by the `author` of the configuration
+/// file at `config_path`, followed by `user.name` from the git configuration of the
+/// repository at `git_root`.
+fn resolve_author(
+	flag: Option<&String>,
+	env_var: &str,
+	config_path: Option<&Path>,
+	git_root: &Path,
+) -> io::Result<Option<String>> {
+	if let Some(author) = flag {
+		return Ok(Some(author.clone()));
+	}
+	if let Ok(author) = std::env::var(env_var) {
+		if !author.trim().is_empty() {
+			return Ok(Some(author.trim().to_string()));
+		}
+	}
+	if let Some(author) = config_path.map(config_author).transpose()?.flatten() {
+		return Ok(Some(author));
+	}
+
+	let Ok(output) = git_command(git_root).args(["config", "user.name"]).output() else {
+		return Ok(None);
+	};
+	let Ok(author) = String::from_utf8(output.stdout) else {
+		return Ok(None);
+	};
+	let author = author.trim();
+	Ok((output.status.success() && !author.is_empty()).then(|| author.to_string()))
+}
+
+/// Name of the project configuration file, which may set the `author`.
+const CONFIG_FILE_NAME: &str = ".copywriter.toml";
+
+/// Returns the nearest `CONFIG_FILE_NAME` in the directory of `path` or one of its
+/// parents.
+fn find_config(path: &Path) -> Option<PathBuf> {
+	let dir = if path.is_file() { path.parent()? } else { path };
+	let dir = fs::canonicalize(if dir.as_os_str().is_empty() {
+		Path::new(".")
+	} else {
+		dir
+	})
+	.ok()?;
+	dir.ancestors()
+		.map(|dir| dir.join(CONFIG_FILE_NAME))
+		.find(|config| config.is_file())
+}
+
+/// Returns the `author` set by the configuration file at `config_path`, if it sets one.
+fn config_author(config_path: &Path) -> io::Result<Option<String>> {
+	let invalid = |message: String| {
+		io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!(
+				"Invalid configuration file {}: {}",
+				config_path.display(),
+				message
+			),
+		)
+	};
+	let config: toml::Table =
+		toml::from_str(&fs::read_to_string(config_path)?).map_err(|e| invalid(e.to_string()))?;
+	match config.get("author") {
+		None => Ok(None),
+		Some(toml::Value::String(author)) if !author.trim().is_empty() => {
+			Ok(Some(author.trim().to_string()))
+		}
+		Some(_) => Err(invalid("\"author\" must be a non-empty string".into())),
+	}
+}
+
+/// Returns the directory git commands run in when `--git-root` is not given: the
+/// processed directory, or the directory containing the processed file.
+fn default_git_root(path: &Path) -> PathBuf {
+	let dir = if path.is_file() {
+		path.parent().unwrap_or(Path::new("."))
+	} else {
+		path
+	};
+	if dir.as_os_str().is_empty() {
+		PathBuf::from(".")
+	} else {
+		dir.to_path_buf()
+	}
+}
+
+/// Prepare a git invocation that operates on the repository at `git_root`.
+fn git_command(git_root: &Path) -> std::process::Command {
+	let mut command = std::process::Command::new("git");
+	command.arg("-C").arg(git_root);
+	command
+}
+
+/// Check that `dir` is inside a git work tree.
+fn is_git_repo(dir: &Path) -> bool {
+	git_command(dir)
+		.args(["rev-parse", "--is-inside-work-tree"])
+		.output()
+		.is_ok_and(|output| output.status.success())
+}
+
+/// Controls which files a directory walk yields and in what order.
+struct WalkOptions {
+	/// Visit entries in file-name order.
+	sorted: bool,
+	/// When set, only files with one of these (lowercase) extensions are yielded.
+	extensions: Option<Vec<&'static str>>,
+	/// Also yield YAML and TOML files, for `--structured-metadata`.
+	metadata_files: bool,
+	/// Also yield files covered by a `--rules` rule.
+	rules: Rules,
+	/// When set, only files whose path relative to the walk root matches are yielded.
+	paths: Option<GlobSet>,
+	/// When set, only files with one of these (lowercase) extensions are yielded, for
+	/// `--check-only-extensions`.
+	check_extensions: Option<Vec<String>>,
+	/// Skip paths listed in `.gitignore` and `.ignore` files.
+	ignore_files: bool,
+	/// The `--include` and `--exclude` globs.
+	overrides: Option<Override>,
+}
+
+/// Compiles `--include` and `--exclude` globs, relative to the walk root `path`, into
+/// one matcher. Excludes are added last so they win over includes.
+fn build_overrides<'a>(
+	path: &Path,
+	includes: impl Iterator<Item = &'a String>,
+	excludes: impl Iterator<Item = &'a String>,
+) -> io::Result<Option<Override>> {
+	let mut builder = OverrideBuilder::new(path);
+	let mut any = false;
+	for glob in includes
+		.map(String::clone)
+		.chain(excludes.map(|glob| format!("!{}", glob)))
+	{
+		builder
+			.add(&glob)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+		any = true;
+	}
+	if !any {
+		return Ok(None);
+	}
+	builder
+		.build()
+		.map(Some)
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// Builds the directory walker for `path`, which prunes ignored and excluded subtrees.
+fn walker(path: &Path, walk_options: &WalkOptions) -> WalkBuilder {
+	let mut walker = WalkBuilder::new(path);
+	walker
+		.standard_filters(walk_options.ignore_files)
+		.hidden(false)
+		.require_git(false);
+	if let Some(overrides) = &walk_options.overrides {
+		walker.overrides(overrides.clone());
+	}
+	if walk_options.sorted {
+		walker.sort_by_file_name(|a, b| a.cmp(b));
+	}
+	walker
+}
+
+/// Returns the directories and source files below `path` that the walk skips because of
+/// ignore files or `--include` and `--exclude` globs, only the topmost of each skipped
+/// subtree, for reporting in dry-run mode.
+fn ignored_paths(path: &Path, walk_options: &WalkOptions) -> Vec<PathBuf> {
+	if path.is_file() || !walk_options.ignore_files && walk_options.overrides.is_none() {
+		return Vec::new();
+	}
+	let walked: Arc<HashSet<PathBuf>> = Arc::new(
+		walker(path, walk_options)
+			.build()
+			.filter_map(|e| e.ok())
+			.map(|e| e.into_path())
+			.collect(),
+	);
+	// Walk again without filters, descending only where the filtered walk did, so each
+	// skipped subtree shows up once, as its top entry.
+	let visited = Arc::clone(&walked);
+	WalkBuilder::new(path)
+		.standard_filters(false)
+		.sort_by_file_name(|a, b| a.cmp(b))
+		.filter_entry(move |e| {
+			e.depth() == 0
+				|| e.path()
+					.parent()
+					.is_some_and(|parent| visited.contains(parent))
+		})
+		.build()
+		.filter_map(|e| e.ok())
+		.map(|e| e.into_path())
+		.filter(|path| !walked.contains(path) && (path.is_dir() || is_source_file(path)))
+		.collect()
+}
+
+/// Compiles `--paths` globs into a single matcher.
+fn build_glob_set<'a>(globs: impl Iterator<Item = &'a str>) -> io::Result<GlobSet> {
+	let mut builder = GlobSetBuilder::new();
+	for glob in globs {
+		let glob = Glob::new(glob).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+		builder.add(glob);
+	}
+	builder
+		.build()
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// Parses a `--time-budget` duration: a number followed by `ms`, `s`, `m`, or `h`, or a
+/// bare number of seconds.
+fn parse_duration(text: &str) -> Result<Duration, String> {
+	let split = text
+		.find(|c: char| !c.is_ascii_digit())
+		.unwrap_or(text.len());
+	let (number, unit) = text.split_at(split);
+	let number: u64 = number
+		.parse()
+		.map_err(|_| format!("invalid duration: {}", text))?;
+	match unit {
+		"ms" => Ok(Duration::from_millis(number)),
+		"" | "s" => Ok(Duration::from_secs(number)),
+		"m" => Ok(Duration::from_secs(number * 60)),
+		"h" => Ok(Duration::from_secs(number * 3600)),
+		_ => Err(format!(
+			"invalid duration unit in {}: use ms, s, m, or h",
+			text
+		)),
+	}
+}
+
+/// Returns the files to process: `path` itself if it is a file, otherwise every
+/// source file below it that passes the filters in `walk_options`.
+fn collect_source_files(path: &Path, walk_options: &WalkOptions) -> Vec<PathBuf> {
+	if path.is_file() {
+		return vec![path.to_path_buf()];
+	}
+
+	walker(path, walk_options)
+		.build()
+		.filter_map(|e| e.ok())
+		.filter(|e| is_walked_file(e.path(), path, walk_options))
+		.map(|e| e.into_path())
+		.collect()
+}
+
+/// Whether `file`, reached by a walk of `root` that the ignore files and globs let
+/// through, is one to process: a source file, a file covered by a rule, or a metadata
+/// file, that passes the language, extension, and path filters of `walk_options`.
+fn is_walked_file(file: &Path, root: &Path, walk_options: &WalkOptions) -> bool {
+	let extension = file
+		.extension()
+		.map(|ext| ext.to_string_lossy().to_lowercase());
+	file.is_file()
+		&& (is_source_file(file)
+			|| walk_options.rules.resolve(file).is_some()
+			|| walk_options.metadata_files && metadata::Format::of(file).is_some()
+			|| extension.is_none() && has_rule_shebang(file, &walk_options.rules))
+		&& walk_options.extensions.as_ref().is_none_or(|extensions| {
+			// `BUILD` and `WORKSPACE` files have no extension; `--lang bazel` takes them
+			// by name.
+			extension
+				.as_ref()
+				.is_some_and(|ext| extensions.contains(&ext.as_str()))
+				|| is_bazel_build_file(file) && extensions.contains(&"bazel")
+		}) && walk_options
+		.check_extensions
+		.as_ref()
+		.is_none_or(|extensions| {
+			extension
+				.as_ref()
+				.is_some_and(|ext| extensions.contains(ext))
+		}) && walk_options
+		.paths
+		.as_ref()
+		.is_none_or(|paths| paths.is_match(file.strip_prefix(root).unwrap_or(file)))
+}
+
+/// Returns those of the `changed` files below `path` that a walk with `walk_options`
+/// yields. Only the directories leading to them are walked, so ignore files and
+/// `--include` and `--exclude` globs apply just as they do to a full walk.
+fn walked_files_among(
+	path: &Path,
+	walk_options: &WalkOptions,
+	changed: &BTreeSet<PathBuf>,
+) -> io::Result<Vec<PathBuf>> {
+	// Event paths may be absolute even when the watched path is not.
+	let root = fs::canonicalize(path)?;
+	let mut wanted = HashSet::new();
+	for file in changed {
+		let Ok(relative) = file
+			.strip_prefix(path)
+			.or_else(|_| file.strip_prefix(&root))
+		else {
+			continue;
+		};
+		let mut prefix = path.to_path_buf();
+		for component in relative.components() {
+			prefix.push(component);
+			wanted.insert(prefix.clone());
+		}
+	}
+	let wanted = Arc::new(wanted);
+	Ok(walker(path, walk_options)
+		.filter_entry(move |e| e.depth() == 0 || wanted.contains(e.path()))
+		.build()
+		.filter_map(|e| e.ok())
+		.filter(|e| e.depth() > 0 && is_walked_file(e.path(), path, walk_options))
+		.map(|e| e.into_path())
+		.collect())
+}
+
+/// Picks the one file per directory that carries the license footer with
+/// `--footer-once-per-directory`: the file named `footer_file` if the directory has
+/// one, otherwise the first of its files in path order.
+fn footer_files(files: &[PathBuf], footer_file: Option<&str>) -> BTreeSet<PathBuf> {
+	let mut chosen: BTreeMap<&Path, &PathBuf> = BTreeMap::new();
+	for file in files {
+		let dir = file.parent().unwrap_or(Path::new(""));
+		let named = footer_file.is_some_and(|name| file.file_name() == Some(name.as_ref()));
+		chosen
+			.entry(dir)
+			.and_modify(|current| {
+				let current_named =
+					footer_file.is_some_and(|name| current.file_name() == Some(name.as_ref()));
+				if named || (!current_named && file < *current) {
+					*current = file;
+				}
+			})
+			.or_insert(file);
+	}
+	chosen.into_values().cloned().collect()
+}
+
+/// Languages accepted by `--lang`, with the file extensions each one covers.
+const LANGUAGES: &[(&str, &[&str])] = &[
+	("rust", &["rs"]),
+	("python", &["py"]),
+	("javascript", &["js", "jsx"]),
+	("typescript", &["ts", "tsx"]),
+	("c", &["c", "h"]),
+	("cpp", &["cpp", "hpp", "h"]),
+	("objcpp", &["mm"]),
+	("cuda", &["cu", "cuh"]),
+	("java", &["java"]),
+	("go", &["go"]),
+	("ruby", &["rb"]),
+	("php", &["php"]),
+	("swift", &["swift"]),
+	("kotlin", &["kt"]),
+	("csharp", &["cs"]),
+	("shell", &["sh", "bash"]),
+	("perl", &["pl", "pm"]),
+	("lua", &["lua"]),
+	("scala", &["scala"]),
+	("css", &["css", "scss"]),
+	("html", &["html"]),
+	("svelte", &["svelte"]),
+	("vue", &["vue"]),
+	("astro", &["astro"]),
+	("xml", &["xml"]),
+	("json", &["json"]),
+	("fsharp", &["fs", "fsx"]),
+	("ocaml", &["ml", "mli"]),
+	("sml", &["sml", "sig"]),
+	("reason", &["re"]),
+	("elixir", &["ex", "exs"]),
+	("erlang", &["erl", "hrl"]),
+	("r", &["r"]),
+	("julia", &["jl"]),
+	("terraform", &["tf", "hcl"]),
+	("vb", &["vb"]),
+	("pascal", &["pas", "pp"]),
+	("bazel", &["bzl", "bazel"]),
+];
+
+/// Returns the extensions covered by the `--lang` name `lang`.
+fn language_extensions(lang: &str) -> &'static [&'static str] {
+	LANGUAGES
+		.iter()
+		.find(|(name, _)| *name == lang)
+		.map_or(&[], |(_, extensions)| *extensions)
+}
+
+/// Settings shared by every file processed in a run.
+struct Options {
+	author: String,
+	license: String,
+	/// The moment the run is stamped with; the copyright year is taken from it.
+	now: DateTime<Utc>,
+	dry_run: bool,
+	/// In dry-run mode, show a unified diff of the changes for each file.
+	diff: bool,
+	/// Part of each file the dry-run diff covers, one of `DIFF_SCOPES`.
+	diff_scope: String,
+	/// Extra diff context lines shown around inserted headers and footers.
+	insertion_context: usize,
+	/// Report files that would change instead of writing them.
+	check: bool,
+	/// In check mode, only count files whose license footer is out of date.
+	stale_footers: bool,
+	/// In check mode, the pattern each file's top block has to match.
+	header_regex: Option<Regex>,
+	/// With `--relicense`, the license every `SPDX-License-Identifier` tag is set to.
+	spdx_id: Option<String>,
+	/// In check mode, fail files with duplicate copyright headers.
+	no_duplicate_headers: bool,
+	/// In check mode, the last line the author's copyright notice may be on.
+	header_within_lines: Option<usize>,
+	/// Files smaller than this many bytes are skipped.
+	min_file_size: u64,
+	/// Move footers that are followed by code to the end of the file.
+	repair_footer: bool,
+	/// Fold the copyright into a license comment block the file already has, in place
+	/// of a header and footer.
+	merge_license_block: bool,
+	/// Add a `Last updated: <date>` line to the footer.
+	timestamp: bool,
+	/// Rewrite existing headers with the canonical comment delimiters and spacing.
+	normalize_prefix: bool,
+	/// Rewrite the notice word and the spacing and commas around the years of existing
+	/// notices to the canonical form.
+	normalize_punctuation: bool,
+	/// Write in the comment style of an existing header rather than the file type's.
+	keep_existing_style: bool,
+	/// Append the current year as a separate entry instead of extending the range
+	/// when years have been skipped.
+	preserve_gaps: bool,
+	/// With `preserve_gaps`, the most entries a year list may have before it collapses
+	/// into a single range.
+	year_list_max: Option<usize>,
+	/// Use `//` instead of `#` comments in Terraform and HCL files.
+	hcl_slash_comments: bool,
+	/// Record the copyright as data in YAML and TOML files instead of as comments.
+	structured_metadata: bool,
+	/// Per-file-type rules from `--rules`, which take precedence over the flags above.
+	rules: Rules,
+	/// A line every file has to end with, after the footer.
+	eof_comment: Option<String>,
+	/// The word that opens notices, from `COPYRIGHT_WORDS`. Without it, existing
+	/// notices keep their form and new ones use `Copyright (c)`.
+	copyright_word: Option<&'static str>,
+	/// Text of a comment line placed directly after the copyright header.
+	header_trailer: Option<String>,
+	/// Order of the copyright lines in a header with several holders, one of
+	/// `HOLDER_ORDERS`.
+	holder_order: String,
+	/// Whitespace put in front of every header and footer line.
+	indent: String,
+	/// Additional footer sections written after the license, as (label, template).
+	footer_sections: Vec<(String, String)>,
+	/// With `--two-pass`, the files whose new content has been written next to them,
+	/// waiting to be renamed into place once every file has been processed.
+	staged: Option<RefCell<Vec<PathBuf>>>,
+	/// When set, only these files get a license footer; see `footer_files`.
+	footer_files: Option<BTreeSet<PathBuf>>,
+	/// Console output format, one of `OUTPUT_FORMATS`.
+	format: String,
+	/// Report the comment style chosen for each file, and why.
+	verbose: bool,
+	/// Suppress per-file progress messages.
+	quiet: bool,
+}
+
+impl Options {
+	/// Print a per-file progress message, unless the output is machine-readable.
+	fn note(&self, message: String) {
+		if !self.quiet {
+			println!("{}", message);
+		}
+	}
+}
+
+/// Diff scopes accepted by `--diff-scope`.
+const DIFF_SCOPES: [&str; 3] = ["both", "header", "footer"];
+
+/// Console output formats accepted by `--format`.
+const OUTPUT_FORMATS: [&str; 2] = ["text", "junit"];
+
+/// The result of processing a single file.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+	/// The file was rewritten, or would be in dry-run and check mode.
+	Changed,
+	/// The file's header and footer are already up to date.
+	Unchanged,
+	/// The file was not processed, for the given reason.
+	Skipped(SkipReason),
+	/// In check mode, the file's top block does not match `--header-regex`.
+	Mismatch,
+}
+
+/// Why a file was skipped.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SkipReason {
+	/// The file is over the size limit.
+	Large,
+	/// The file is under the `--min-file-size` floor.
+	Small,
+	/// The file contains NUL bytes.
+	Binary,
+	/// The file is not valid UTF-8.
+	NotUtf8,
+	/// The YAML file has no top-level mapping to add metadata to.
+	NoMapping,
+	/// The file has a source extension but holds something else, such as a FreeMind map.
+	NotSource,
+}
+
+impl SkipReason {
+	/// The reason as written to `--skipped-report`.
+	fn as_str(self) -> &'static str {
+		match self {
+			SkipReason::Large => "large",
+			SkipReason::Small => "too-small",
+			SkipReason::Binary => "binary",
+			SkipReason::NotUtf8 => "not-utf8",
+			SkipReason::NoMapping => "no-top-level-mapping",
+			SkipReason::NotSource => "not-source",
+		}
+	}
+}
+
+/// Returns why `outcome` fails the check, or `None` if it passes (or this is not a
+/// check run).
+fn check_failure(outcome: Outcome, options: &Options) -> Option<&'static str> {
+	if !options.check {
+		return None;
+	}
+	match outcome {
+		Outcome::Changed if options.stale_footers => Some("License footer is stale"),
+		Outcome::Changed => Some("Copyright header or license footer needs updating"),
+		Outcome::Mismatch => Some("Header does not match --header-regex"),
+		Outcome::Unchanged | Outcome::Skipped(_) => None,
+	}
+}
+
+/// Render the `--audit-json` document for `files`: for each, whether it has a
+/// copyright header and a license footer, the holders and years of its notices
+/// (outside the footer), the licenses it declares, the license file that applies to it,
+/// and why it would be skipped, if it would be.
+fn render_audit_json(
+	files: &[PathBuf],
+	licenses: &LicenseLookup,
+	min_file_size: u64,
+) -> io::Result<String> {
+	let mut entries = Vec::new();
+	for file in files {
+		let license_file = file
+			.parent()
+			.and_then(|dir| licenses.find(dir))
+			.map(|license| license.display().to_string());
+		let content = match read_source(file, min_file_size)? {
+			Ok(content) => content,
+			Err(reason) => {
+				entries.push(serde_json::json!({
+					"path": file.display().to_string(),
+					"skipped": reason.as_str(),
+					"header": null,
+					"footer": null,
+					"holders": null,
+					"spdx_ids": null,
+					"license_file": license_file,
+				}));
+				continue;
+			}
+		};
+		let (comment_start, _, comment_end) = get_comment_style(file);
+		let footers = find_footer_blocks(&content, comment_start, comment_end, &["License"]);
+		let mut header_text = content.clone();
+		for footer in footers.iter().rev() {
+			header_text.replace_range(footer.clone(), "");
+		}
+		let holders: Vec<_> = parse_copyright_notices(&header_text, comment_end)
+			.into_iter()
+			.map(|notice| {
+				serde_json::json!({
+					"holder": notice.holder,
+					"years": notice.years,
+					"last_year": notice.last_year,
+				})
+			})
+			.collect();
+		entries.push(serde_json::json!({
+			"path": file.display().to_string(),
+			"skipped": null,
+			"header": !holders.is_empty(),
+			"footer": !footers.is_empty(),
+			"holders": holders,
+			"spdx_ids": declared_licenses(&content),
+			"license_file": license_file,
+		}));
+	}
+	let audit = serde_json::json!({ "files": entries });
+	Ok(serde_json::to_string_pretty(&audit).unwrap() + "\n")
+}
+
+/// Render the aggregate counts of a run for `--summary-json`, in total and per file
+/// extension. `failed` is the file whose error ended the run early, if any.
+fn render_summary_json(results: &[(PathBuf, Outcome)], failed: Option<&Path>) -> String {
+	fn key(outcome: Outcome) -> &'static str {
+		match outcome {
+			Outcome::Changed => "updated",
+			Outcome::Unchanged => "unchanged",
+			Outcome::Skipped(_) => "skipped",
+			Outcome::Mismatch => "error",
+		}
+	}
+	fn counts() -> BTreeMap<&'static str, usize> {
+		["updated", "unchanged", "skipped", "error"]
+			.into_iter()
+			.map(|key| (key, 0))
+			.collect()
+	}
+
+	let mut totals = counts();
+	let mut extensions: BTreeMap<String, BTreeMap<&str, usize>> = BTreeMap::new();
+	let files = results
+		.iter()
+		.map(|(file, outcome)| (file.as_path(), key(*outcome)))
+		.chain(failed.map(|file| (file, "error")));
+	for (file, key) in files {
+		let extension = file
+			.extension()
+			.map(|ext| ext.to_string_lossy().to_lowercase())
+			.unwrap_or_default();
+		*totals.get_mut(key).unwrap() += 1;
+		*extensions
+			.entry(extension)
+			.or_insert_with(counts)
+			.get_mut(key)
+			.unwrap() += 1;
+	}
+
+	let summary = serde_json::json!({ "totals": totals, "extensions": extensions });
+	serde_json::to_string_pretty(&summary).unwrap() + "\n"
+}
+
+/// Render check results as a JUnit XML report with one test case per file.
+fn render_junit(results: &[(PathBuf, Outcome)], options: &Options) -> String {
+	let failures: Vec<_> = results
+		.iter()
+		.map(|(_, outcome)| check_failure(*outcome, options))
+		.collect();
+	let skipped = results
+		.iter()
+		.filter(|(_, outcome)| matches!(outcome, Outcome::Skipped(_)))
+		.count();
+
+	let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+	xml.push_str(&format!(
+		"<testsuite name=\"copywriter\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n",
+		results.len(),
+		failures.iter().flatten().count(),
+		skipped
+	));
+	for ((file, outcome), failure) in results.iter().zip(&failures) {
+		let name = xml_escape(&file.display().to_string());
+		match (failure, outcome) {
+			(Some(message), _) => xml.push_str(&format!(
+				"  <testcase classname=\"copywriter\" name=\"{}\">\n    <failure message=\"{}\"/>\n  </testcase>\n",
+				name, message
+			)),
+			(None, Outcome::Skipped(_)) => xml.push_str(&format!(
+				"  <testcase classname=\"copywriter\" name=\"{}\">\n    <skipped/>\n  </testcase>\n",
+				name
+			)),
+			(None, _) => xml.push_str(&format!(
+				"  <testcase classname=\"copywriter\" name=\"{}\"/>\n",
+				name
+			)),
+		}
+	}
+	xml.push_str("</testsuite>\n");
+	xml
+}
+
+/// Escape `text` for use in XML attribute values.
+fn xml_escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+/// Compile a user-supplied `--header-regex`, expanding `{author}` to the literal
+/// author name and `{year}` to a year list ending in the current year (so `2025`,
+/// `2019-2025` and `2018-2020, 2025` all match when the current year is 2025).
+fn expand_header_regex(pattern: &str, author_name: &str, current_year: i32) -> io::Result<Regex> {
+	let expanded = pattern
+		.replace("{author}", &regex::escape(author_name))
+		.replace(
+			"{year}",
+			&format!(r"(?:\d{{4}}(?:-\d{{4}})?, ?)*(?:\d{{4}}-)?{}", current_year),
+		);
+	Regex::new(&expanded).map_err(|e| {
+		io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("Invalid --header-regex: {}", e),
+		)
+	})
+}
+
+/// Returns the leading lines of `content` up to the first blank line, which is where
+/// the copyright header lives. A shebang line, which the header sits below, is skipped.
+fn top_block(content: &str) -> &str {
+	let mut content = content.trim_start_matches(['\u{FEFF}', '\r', '\n']);
+	if content.starts_with("#!") && !content.starts_with("#![") {
+		let shebang_end = content.find('\n').map_or(content.len(), |i| i + 1);
+		content = content[shebang_end..].trim_start_matches(['\r', '\n']);
+	}
+	match content.find("\n\n") {
+		Some(end) => &content[..end],
+		None => content,
+	}
+}
+
+/// Returns the time to stamp the run with. In deterministic mode this honors
+/// `SOURCE_DATE_EPOCH` (seconds since the Unix epoch) so that repeated runs
+/// produce identical output regardless of the wall clock.
+fn reference_time(deterministic: bool) -> io::Result<DateTime<Utc>> {
+	if deterministic {
+		if let Ok(epoch) = std::env::var("SOURCE_DATE_EPOCH") {
+			return epoch
+				.trim()
+				.parse::<i64>()
+				.ok()
+				.and_then(|secs| DateTime::from_timestamp(secs, 0))
+				.ok_or_else(|| {
+					io::Error::new(
+						io::ErrorKind::InvalidInput,
+						format!("Invalid SOURCE_DATE_EPOCH: {}", epoch),
+					)
+				});
+		}
+	}
+	Ok(Utc::now())
+}
+
+/// License filenames searched for when `--license-names` is not given.
+const DEFAULT_LICENSE_NAMES: [&str; 3] = ["LICENSE", "LICENSE.md", "LICENSE.txt"];
+
+/// Normalize the whitespace of `license`, so footers don't change with the quirks of
+/// the license file: line endings become `\n`, runs of spaces and tabs within a line
+/// become one space (indentation is kept), trailing whitespace and repeated or
+/// surrounding blank lines are dropped. With `wrap`, each paragraph is also rewrapped
+/// to at most that many columns.
+fn normalize_license(license: &str, wrap: Option<usize>) -> String {
+	let space_regex = Regex::new(r"[ \t]+").unwrap();
+	let lines: Vec<String> = license
+		.lines()
+		.map(|line| {
+			let body = line.trim_start();
+			let indent = &line[..line.len() - body.len()];
+			format!(
+				"{}{}",
+				indent,
+				space_regex.replace_all(body.trim_end(), " ")
+			)
+		})
+		.collect();
+	let paragraphs = lines
+		.split(|line| line.is_empty())
+		.filter(|paragraph| !paragraph.is_empty())
+		.map(|paragraph| match wrap {
+			Some(width) => wrap_words(&paragraph.join(" "), width),
+			None => paragraph.join("\n"),
+		});
+	paragraphs.collect::<Vec<String>>().join("\n\n")
+}
+
+/// Break `text` into lines of at most `width` columns at spaces. Words longer than
+/// `width` get a line of their own.
+fn wrap_words(text: &str, width: usize) -> String {
+	let mut lines: Vec<String> = Vec::new();
+	for word in text.split_whitespace() {
+		match lines.last_mut() {
+			Some(line) if line.len() + 1 + word.len() <= width => {
+				line.push(' ');
+				line.push_str(word);
+			}
+			_ => lines.push(word.to_string()),
+		}
+	}
+	lines.join("\n")
+}
+
+/// Keep only the first `max_lines` lines of `license`, followed by a note pointing to
+/// `license_name` for the rest. Licenses that are short enough are returned as is.
+fn truncate_license(license: &str, max_lines: usize, license_name: &str) -> String {
+	let lines: Vec<&str> = license.lines().collect();
+	if lines.len() <= max_lines {
+		return license.to_string();
+	}
+	format!(
+		"{}\n\nSee {} for the full license text.",
+		lines[..max_lines].join("\n").trim_end(),
+		license_name
+	)
+}
+
+/// Search for a license file named one of `license_names` in the current or
+/// parent directories, returning its path and contents.
+fn find_and_read_license(
+	start_path: &str,
+	license_names: &[&str],
+) -> io::Result<(PathBuf, String)> {
+	let license_path = find_license(Path::new(start_path), license_names)
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "License file not found"))?;
+	let content = fs::read_to_string(&license_path)?;
+	Ok((license_path, content))
+}
+
+/// Returns the path of the license file that applies to `start_path`: the first of
+/// `license_names` found in its directory or the nearest parent directory.
+fn find_license(start_path: &Path, license_names: &[&str]) -> Option<PathBuf> {
+	LicenseLookup::new(license_names).find(start_path)
+}
+
+/// Finds license files like `find_license`, remembering the result for every directory
+/// it passes on the way up, so lookups for many files in the same tree each walk only
+/// the directories not seen before. The memo is shared between threads, and each
+/// directory is searched only once even when several threads ask for it at once.
+struct LicenseLookup<'a> {
+	license_names: &'a [&'a str],
+	/// The license file found for each directory visited so far.
+	found: Mutex<HashMap<PathBuf, Arc<OnceLock<Option<PathBuf>>>>>,
+	/// How many directories have been searched for a license file.
+	#[cfg(test)]
+	probes: std::sync::atomic::AtomicUsize,
+}
+
+impl<'a> LicenseLookup<'a> {
+	fn new(license_names: &'a [&'a str]) -> Self {
+		LicenseLookup {
+			license_names,
+			found: Mutex::default(),
+			#[cfg(test)]
+			probes: Default::default(),
+		}
+	}
+
+	/// The license file for `start_path`, searched for from its directory upwards.
+	fn find(&self, start_path: &Path) -> Option<PathBuf> {
+		let dir = if start_path.is_file() {
+			start_path.parent().unwrap_or(Path::new("."))
+		} else {
+			start_path
+		};
+		self.find_from(dir, 100)
+	}
+
+	/// The license file in `dir` or, up to `depth` levels up, its nearest parent.
+	fn find_from(&self, dir: &Path, depth: usize) -> Option<PathBuf> {
+		let entry = Arc::clone(
+			self.found
+				.lock()
+				.unwrap()
+				.entry(dir.to_path_buf())
+				.or_default(),
+		);
+		entry
+			.get_or_init(|| {
+				#[cfg(test)]
+				self.probes
+					.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+				self.license_names
+					.iter()
+					.map(|license_filename| dir.join(license_filename))
+					.find(|license_path| license_path.exists())
+					.or_else(|| match dir.parent() {
+						Some(parent) if depth > 1 => self.find_from(parent, depth - 1),
+						_ => None,
+					})
+			})
+			.clone()
+	}
+
+	/// Look up the license files of all `dirs` on as many threads as there are CPUs,
+	/// so that later calls to `find` for them are answered from the memo.
+	fn resolve_parallel(&self, dirs: &[PathBuf]) {
+		let threads = std::thread::available_parallelism().map_or(1, usize::from);
+		let chunk_size = dirs.len().div_ceil(threads).max(1);
+		std::thread::scope(|scope| {
+			for chunk in dirs.chunks(chunk_size) {
+				scope.spawn(move || {
+					for dir in chunk {
+						self.find(dir);
+					}
+				});
+			}
+		});
+	}
+}
+
+/// Check whether a file is a source file based on its extension.
+fn is_source_file(path: &Path) -> bool {
+	let source_extensions = [
+		".rs", ".py", ".js", ".jsx", ".ts", ".tsx", ".c", ".cpp", ".h", ".hpp", ".java", ".go",
+		".rb", ".php", ".swift", ".kt", ".cs", ".sh", ".bash", ".pl", ".pm", ".lua", ".scala",
+		".css", ".scss", ".html", ".xml", ".json", ".fs", ".fsx", ".ml", ".mli", ".re", ".ex",
+		".exs", ".erl", ".hrl", ".r", ".jl", ".tf", ".hcl", ".vb", ".pas", ".pp", ".sml", ".sig",
+		".svelte", ".vue", ".astro", ".mm", ".cu", ".cuh", ".bzl",
+	];
+
+	if let Some(ext) = path.extension() {
+		let ext = format!(".{}", ext.to_string_lossy().to_lowercase());
+		source_extensions.contains(&ext.as_str())
+			|| is_workflow_file(path)
+			|| is_bazel_build_file(path)
+	} else {
+		is_bazel_build_file(path)
+	}
+}
+
+/// File names of Bazel build files, which have `#` comments.
+const BAZEL_FILE_NAMES: [&str; 5] = [
+	"BUILD",
+	"BUILD.bazel",
+	"WORKSPACE",
+	"WORKSPACE.bazel",
+	"MODULE.bazel",
+];
+
+/// Check whether `path` is a Bazel build file, recognized by its name alone.
+fn is_bazel_build_file(path: &Path) -> bool {
+	path.file_name()
+		.is_some_and(|name| BAZEL_FILE_NAMES.iter().any(|bazel| name == *bazel))
+}
+
+/// Check whether `path` is a GitHub Actions workflow, a YAML file in `.github/workflows`.
+/// Workflows always get a `#` comment header, even with `--structured-metadata`, since
+/// GitHub rejects unknown top-level keys.
+fn is_workflow_file(path: &Path) -> bool {
+	let is_yaml = path
+		.extension()
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("yml") || ext.eq_ignore_ascii_case("yaml"));
+	let mut dirs = path.parent().into_iter().flat_map(Path::iter).rev();
+	is_yaml && dirs.next() == Some("workflows".as_ref()) && dirs.next() == Some(".github".as_ref())
+}
+
+/// Words that mark a comment block as a license notice, for
+/// `--merge-into-existing-license-block`. They are matched regardless of case.
+const LICENSE_KEYWORDS: [&str; 4] = [
+	"license",
+	"permission is hereby granted",
+	"redistribution and use",
+	"all rights reserved",
+];
+
+/// Find the first comment block in `content`, outside the `footers` ranges, that reads
+/// like a license notice, i.e. mentions one of `LICENSE_KEYWORDS`. The range covers the
+/// block's whole lines, without the final line break.
+fn find_license_block(
+	content: &str,
+	comment_start: &str,
+	comment_end: &str,
+	footers: &[Range<usize>],
+) -> Option<Range<usize>> {
+	let start_marker = comment_start.trim();
+	let end_marker = comment_end.trim();
+	let mut lines = Vec::new();
+	let mut offset = 0;
+	for line in content.split_inclusive('\n') {
+		lines.push(offset..offset + line.trim_end_matches(['\r', '\n']).len());
+		offset += line.len();
+	}
+
+	let mut i = 0;
+	while i < lines.len() {
+		let line = content[lines[i].clone()].trim_start();
+		if !line.starts_with(start_marker)
+			|| footers
+				.iter()
+				.any(|footer| footer.contains(&lines[i].start))
+		{
+			i += 1;
+			continue;
+		}
+		let mut last = i;
+		if start_marker == end_marker {
+			while last + 1 < lines.len()
+				&& content[lines[last + 1].clone()]
+					.trim_start()
+					.starts_with(start_marker)
+			{
+				last += 1;
+			}
+		} else if !line[start_marker.len()..].contains(end_marker) {
+			last += 1;
+			while last < lines.len() && !content[lines[last].clone()].contains(end_marker) {
+				last += 1;
+			}
+			if last == lines.len() {
+				return None;
+			}
+		}
+		let block = lines[i].start..lines[last].end;
+		let text = content[block.clone()].to_lowercase();
+		if LICENSE_KEYWORDS
+			.iter()
+			.any(|keyword| text.contains(keyword))
+		{
+			return Some(block);
+		}
+		i = last + 1;
+	}
+	None
+}
+
+/// Returns a tuple of (block comment start, comment prefix, block comment end) for a file.
+fn get_comment_style(path: &Path) -> (&'static str, &'static str, &'static str) {
+	if let Some(ext) = path.extension() {
+		match ext.to_string_lossy().to_lowercase().as_str() {
+			// C-style comments, including Objective-C++ (`.mm`) and CUDA (`.cu`, `.cuh`).
+			"rs" | "c" | "cpp" | "h" | "hpp" | "js" | "jsx" | "ts" | "tsx" | "go" | "java"
+			| "swift" | "kt" | "scala" | "css" | "scss" | "cs" | "re" | "mm" | "cu" | "cuh" => {
+				("/*", " * ", " */")
+			}
+			// ML-style comments (OCaml, Standard ML, and F#).
+			"ml" | "mli" | "sml" | "sig" | "fs" | "fsx" => ("(*", " * ", " *)"),
+			// Hash-style comments.
+			// R Markdown (`.Rmd`) is deliberately not covered by "r": it opens with YAML
+			// front matter, which a header must not precede.
+			// Terraform and HCL also accept `//`; see `--hcl-comment`.
+			// YAML only reaches here for GitHub Actions workflows; see `is_workflow_file`.
+			// Bazel's `BUILD.bazel` and friends count as `.bazel`; see `is_bazel_build_file`.
+			"py" | "rb" | "sh" | "bash" | "pl" | "pm" | "php" | "ex" | "exs" | "r" | "jl"
+			| "tf" | "hcl" | "yml" | "yaml" | "bzl" | "bazel" => ("#", "# ", "#"),
+			// Percent-style comments (Erlang).
+			"erl" | "hrl" => ("%", "% ", "%"),
+			// Visual Basic line comments.
+			"vb" => ("'", "' ", "'"),
+			// Pascal brace comments.
+			"pas" | "pp" => ("{", " ", "}"),
+			// Lua-style comments.
+			"lua" => ("--[[", "-- ", "--]]"),
+			// HTML/XML-style comments. Svelte, Vue, and Astro components take the header at
+			// the very top, ahead of any `<script>` block or Astro's `---` frontmatter fence.
+			// A header that an earlier tool put inside a `<script>` block in its own
+			// comment style is still recognized by `find_holder_line` and updated there.
+			"html" | "xml" | "svelte" | "vue" | "astro" => ("<!--", " ", "-->"),
+			_ => ("/*", " * ", " */"),
+		}
+	} else {
+		("#", "# ", "#")
+	}
+}
+
+/// Interpreters recognized in shebang lines, with an extension of their language.
+const SHEBANG_INTERPRETERS: &[(&str, &str)] = &[
+	("sh", "sh"),
+	("bash", "sh"),
+	("zsh", "sh"),
+	("dash", "sh"),
+	("python", "py"),
+	("python3", "py"),
+	("ruby", "rb"),
+	("perl", "pl"),
+	("php", "php"),
+	("node", "js"),
+	("lua", "lua"),
+	("Rscript", "r"),
+	("julia", "jl"),
+	("elixir", "ex"),
+	("escript", "erl"),
+];
+
+/// Returns the comment style of the language named by the `#!` line `content` opens
+/// with, for files without an extension. Both `#!/usr/bin/python3` and
+/// `#!/usr/bin/env python3` name `python3`. Interpreters of `--lang-defs` languages
+/// come before the built-in ones.
+fn shebang_comment_style(
+	content: &str,
+	rules: &Rules,
+) -> Option<(&'static str, &'static str, &'static str)> {
+	let program = shebang_program(content)?;
+	if let Some(style) = rules
+		.resolve_interpreter(program)
+		.and_then(|rule| rule.comment_style)
+	{
+		return Some(style);
+	}
+	let (_, ext) = SHEBANG_INTERPRETERS
+		.iter()
+		.find(|(name, _)| *name == program)?;
+	Some(get_comment_style(Path::new(&format!("script.{}", ext))))
+}
+
+/// Returns the program named by the `#!` line `content` opens with. Both
+/// `#!/usr/bin/python3` and `#!/usr/bin/env python3` name `python3`.
+fn shebang_program(content: &str) -> Option<&str> {
+	let line = content.strip_prefix("#!")?.lines().next()?;
+	let mut words = line.split_whitespace();
+	let program = words.next()?.rsplit('/').next()?;
+	if program == "env" {
+		return words.find(|word| !word.starts_with('-'));
+	}
+	Some(program)
+}
+
+/// Whether the extensionless file at `path` opens with a `#!` line naming one of the
+/// interpreters of a `--lang-defs` language. Only the start of the file is read.
+fn has_rule_shebang(path: &Path, rules: &Rules) -> bool {
+	if !rules.has_interpreters() {
+		return false;
+	}
+	let mut head = Vec::with_capacity(256);
+	let read = fs::File::open(path).and_then(|file| file.take(256).read_to_end(&mut head));
+	read.is_ok()
+		&& shebang_program(&String::from_utf8_lossy(&head))
+			.is_some_and(|program| rules.resolve_interpreter(program).is_some())
+}
+
+/// Comment styles an existing header may be written in, for `--keep-existing-style`.
+const COMMENT_STYLES: &[(&str, &str, &str)] = &[
+	("/*", " * ", " */"),
+	("(*", " * ", " *)"),
+	("<!--", " ", "-->"),
+	("--[[", "-- ", "--]]"),
+	("//", "// ", "//"),
+	("--", "-- ", "--"),
+	("#", "# ", "#"),
+	("%", "% ", "%"),
+	(";", "; ", ";"),
+	("'", "' ", "'"),
+	("{", " ", "}"),
+];
+
+/// Returns the comment style of `author`'s existing copyright header in `content`, if
+/// there is one and its opening delimiter is one of `COMMENT_STYLES`.
+fn existing_comment_style(
+	content: &str,
+	author: &str,
+) -> Option<(&'static str, &'static str, &'static str)> {
+	let header_regex = Regex::new(&format!(
+		r"(?m)^[ \t]*(\S+?)[ \t]*(?:{})[ \t]*(?:{}){}{}",
+		NOTICE_WORD_PATTERN,
+		YEARS_PATTERN,
+		HOLDER_GAP_PATTERN,
+		regex::escape(author)
+	))
+	.unwrap();
+	let opener = header_regex.captures(top_block(content))?.get(1)?.as_str();
+	COMMENT_STYLES
+		.iter()
+		.find(|(start, _, _)| *start == opener)
+		.copied()
+}
+
+/// A copyright notice found in a file, e.g. `Copyright (c) 2019-2024 Jane Doe`.
+struct CopyrightNotice {
+	holder: String,
+	years: String,
+	last_year: i32,
+}
+
+/// Find every copyright notice in `content`. The trailing `comment_end` delimiter of
+/// single-line headers is not part of the holder's name and is stripped.
+fn parse_copyright_notices(content: &str, comment_end: &str) -> Vec<CopyrightNotice> {
+	let notice_regex = notice_regex();
+	notice_regex
+		.captures_iter(content)
+		.filter_map(|caps| {
+			let last_year = parse_years(&caps[2]).iter().map(|(_, end)| *end).max()?;
+			let holder = caps[3].trim();
+			let holder = holder
+				.strip_suffix(comment_end.trim())
+				.unwrap_or(holder)
+				.trim();
+			if holder.is_empty() {
+				return None;
+			}
+			Some(CopyrightNotice {
+				holder: holder.to_string(),
+				years: caps[2].to_string(),
+				last_year,
+			})
+		})
+		.collect()
+}
+
+/// Find the copyright notices of `file` outside its license footers, whose license
+/// text carries the licensor's own notice.
+fn file_notices(file: &Path, content: &str) -> Vec<CopyrightNotice> {
+	let (comment_start, _, comment_end) = get_comment_style(file);
+	let mut content = content.to_string();
+	for footer in find_footer_blocks(&content, comment_start, comment_end, &["License"])
+		.into_iter()
+		.rev()
+	{
+		content.replace_range(footer, "");
+	}
+	parse_copyright_notices(&content, comment_end)
+}
+
+/// Output formats for the holders report.
+const REPORT_FORMATS: [&str; 4] = ["text", "json", "csv", "markdown"];
+
+/// Licenses recognized by their text when a file has no SPDX identifier, as
+/// (identifier, phrases that must all appear). More specific licenses come first, since
+/// e.g. the LGPL also mentions the GPL.
+const KNOWN_LICENSE_TEXTS: &[(&str, &[&str])] = &[
+	("MIT", &["Permission is hereby granted, free of charge"]),
+	("Apache-2.0", &["Apache License", "Version 2.0"]),
+	(
+		"LGPL-3.0",
+		&["GNU Lesser General Public License", "version 3"],
+	),
+	(
+		"AGPL-3.0",
+		&["GNU Affero General Public License", "version 3"],
+	),
+	("GPL-3.0", &["GNU General Public License", "version 3"]),
+	("GPL-2.0", &["GNU General Public License", "version 2"]),
+	("MPL-2.0", &["Mozilla Public License", "2.0"]),
+	(
+		"BSD-3-Clause",
+		&[
+			"Redistribution and use in source and binary forms",
+			"Neither the name",
+		],
+	),
+	(
+		"BSD-2-Clause",
+		&["Redistribution and use in source and binary forms"],
+	),
+];
+
+/// The licenses a file declares: the identifiers of its `SPDX-License-Identifier`
+/// expressions (without `WITH` exceptions), or failing that, the first license whose
+/// text it contains.
+fn declared_licenses(content: &str) -> Vec<String> {
+	let spdx_regex = Regex::new(r"SPDX-License-Identifier:\s*([^\r\n]+)").unwrap();
+	let mut licenses = Vec::new();
+	for caps in spdx_regex.captures_iter(content) {
+		let expression = caps[1].replace(['(', ')'], " ");
+		let mut tokens = expression.split_whitespace();
+		while let Some(token) = tokens.next() {
+			match token {
+				"AND" | "OR" => {}
+				"WITH" => {
+					tokens.next();
+				}
+				// Closing comment delimiters on the same line are not identifiers.
+				_ if !token.chars().next().is_some_and(char::is_alphanumeric) => {}
+				_ => licenses.push(token.to_string()),
+			}
+		}
+	}
+	if licenses.is_empty() {
+		let lowercase = content.to_lowercase();
+		if let Some((license, _)) = KNOWN_LICENSE_TEXTS.iter().find(|(_, phrases)| {
+			phrases
+				.iter()
+				.all(|phrase| lowercase.contains(&phrase.to_lowercase()))
+		}) {
+			licenses.push(license.to_string());
+		}
+	}
+	licenses
+}
+
+/// Collect every copyright holder credited in `files`, outside their license footers,
+/// together with the most recent year they appear with anywhere.
+fn aggregate_holders(files: &[PathBuf]) -> BTreeMap<String, i32> {
+	let mut latest_years: BTreeMap<String, i32> = BTreeMap::new();
+	for file in files {
+		let Ok(content) = fs::read_to_string(file) else {
+			continue;
+		};
+		for notice in file_notices(file, &content) {
+			let latest = latest_years
+				.entry(notice.holder)
+				.or_insert(notice.last_year);
+			*latest = (*latest).max(notice.last_year);
+		}
+	}
+	latest_years
+}
+
+/// Returns the files among `files` that carry a copyright notice outside their license
+/// footers, with the latest year any of those notices runs to, ordered from the oldest
+/// year to the most recent.
+fn oldest_files(files: &[PathBuf]) -> Vec<(i32, PathBuf)> {
+	let mut oldest: Vec<(i32, PathBuf)> = files
+		.iter()
+		.filter_map(|file| {
+			let content = fs::read_to_string(file).ok()?;
+			let last_year = file_notices(file, &content)
+				.iter()
+				.map(|notice| notice.last_year)
+				.max()?;
+			Some((last_year, file.clone()))
+		})
+		.collect();
+	oldest.sort();
+	oldest
+}
+
+/// Render the holders report in `format` (one of `REPORT_FORMATS`). Holders whose
+/// latest year is before `current_year` are flagged as stale.
+fn render_holders_report(
+	latest_years: &BTreeMap<String, i32>,
+	current_year: i32,
+	format: &str,
+) -> String {
+	let rows = latest_years
+		.iter()
+		.map(|(holder, latest)| (holder, *latest, *latest < current_year));
+	match format {
+		"json" => {
+			let holders: Vec<_> = rows
+				.map(|(holder, latest, stale)| {
+					serde_json::json!({ "holder": holder, "latest_year": latest, "stale": stale })
+				})
+				.collect();
+			serde_json::to_string_pretty(&holders).unwrap() + "\n"
+		}
+		"csv" => {
+			let mut out = String::from("holder,latest_year,stale\n");
+			for (holder, latest, stale) in rows {
+				let holder = if holder.contains([',', '"', '\n']) {
+					format!("\"{}\"", holder.replace('"', "\"\""))
+				} else {
+					holder.clone()
+				};
+				out.push_str(&format!("{},{},{}\n", holder, latest, stale));
+			}
+			out
+		}
+		"markdown" => {
+			let mut out = String::from("| Holder | Latest year | Stale |\n| --- | --- | --- |\n");
+			for (holder, latest, stale) in rows {
+				let stale = if stale { "yes" } else { "no" };
+				out.push_str(&format!(
+					"| {} | {} | {} |\n",
+					holder.replace('|', "\\|"),
+					latest,
+					stale
+				));
+			}
+			out
+		}
+		_ => rows
+			.map(|(holder, latest, stale)| {
+				if stale {
+					format!("{}: {} (stale)\n", holder, latest)
+				} else {
+					format!("{}: {}\n", holder, latest)
+				}
+			})
+			.collect(),
+	}
+}
+
+/// Lines of context shown around each change in dry-run diffs.
+const DIFF_CONTEXT: usize = 3;
+
+/// Render the change from `old` to `new` as a unified diff. Hunks that only insert
+/// lines get `insertion_context` lines of context on top of the usual amount, which
+/// helps review where a header or footer was placed.
+fn unified_diff(file_path: &Path, old: &str, new: &str, insertion_context: usize) -> String {
+	let diff = TextDiff::from_lines(old, new);
+	let path = file_path.display().to_string();
+	let mut out = format!("--- {}\n+++ {}\n", path, path);
+	for ops in group_diff_ops(diff.ops(), insertion_context) {
+		out.push_str(&UnifiedDiffHunk::new(ops, &diff, true).to_string());
+	}
+	out.trim_end().to_string()
+}
+
+/// Split diff `ops` into hunks, keeping `DIFF_CONTEXT` equal lines around each
+/// change, or `DIFF_CONTEXT + insertion_context` around pure insertions.
+fn group_diff_ops(ops: &[DiffOp], insertion_context: usize) -> Vec<Vec<DiffOp>> {
+	let radius = |op: Option<&DiffOp>| match op {
+		Some(DiffOp::Insert { .. }) => DIFF_CONTEXT + insertion_context,
+		Some(_) => DIFF_CONTEXT,
+		None => 0,
+	};
+
+	let mut hunks = Vec::new();
+	let mut hunk = Vec::new();
+	for (i, op) in ops.iter().enumerate() {
+		let DiffOp::Equal {
+			old_index,
+			new_index,
+			len,
+		} = *op
+		else {
+			hunk.push(*op);
+			continue;
+		};
+
+		let previous = i.checked_sub(1).and_then(|i| ops.get(i));
+		let next = ops.get(i + 1);
+		let leading = radius(previous).min(len);
+		let trailing = radius(next).min(len);
+		if !hunk.is_empty() && leading + trailing >= len {
+			hunk.push(*op);
+			continue;
+		}
+
+		if !hunk.is_empty() {
+			hunk.push(DiffOp::Equal {
+				old_index,
+				new_index,
+				len: leading,
+			});
+			hunks.push(std::mem::take(&mut hunk));
+		}
+		if next.is_some() && trailing > 0 {
+			hunk.push(DiffOp::Equal {
+				old_index: old_index + len - trailing,
+				new_index: new_index + len - trailing,
+				len: trailing,
+			});
+		}
+	}
+	if hunk.iter().any(|op| !matches!(op, DiffOp::Equal { .. })) {
+		hunks.push(hunk);
+	}
+	hunks
+}
+
+/// Matches the word that opens a copyright notice, in any of the forms
+/// `--copyright-word` can produce and the variants hand-written headers use, such as
+/// `Copyright(c)` and `Copyright ©`.
+const NOTICE_WORD_PATTERN: &str = r"Copyright[ \t]*\([cC]\)|Copyright[ \t]*©|Copyright|\([cC]\)|©";
+
+/// Notice words accepted by `--copyright-word`, with the word each one writes.
+const COPYRIGHT_WORDS: &[(&str, &str)] = &[
+	("both", "Copyright (c)"),
+	("spelled", "Copyright"),
+	("symbol", "(c)"),
+];
+
+/// Matches the years of a copyright notice: a single year, a `start-end` range, or a
+/// comma-separated list of those, e.g. `2018-2020, 2025`.
+const YEARS_PATTERN: &str = r"\d{4}(?:-\d{4})?(?:, ?\d{4}(?:-\d{4})?)*";
+
+/// Matches the spacing between a notice's years and its holder: spaces, or a comma
+/// followed by any number of them, as in `Copyright © 2020, Jane Doe`.
+const HOLDER_GAP_PATTERN: &str = r"(?:,[ \t]*|[ \t]+)";
+
+/// A regex matching a copyright notice, capturing the notice word, the years, and the
+/// rest of the line from the holder on. Spaces before the years may be missing.
+fn notice_regex() -> Regex {
+	Regex::new(&format!(
+		r"({})[ \t]*({}){}([^\r\n]+)",
+		NOTICE_WORD_PATTERN, YEARS_PATTERN, HOLDER_GAP_PATTERN
+	))
+	.unwrap()
+}
+
+/// Whether `text` consists of exactly a year list matched by `YEARS_PATTERN`.
+fn is_years(text: &str) -> bool {
+	Regex::new(&format!("^(?:{})$", YEARS_PATTERN))
+		.unwrap()
+		.is_match(text)
+}
+
+/// Parse years matched by `YEARS_PATTERN` into `(start, end)` ranges.
+fn parse_years(years: &str) -> Vec<(i32, i32)> {
+	years
+		.split(',')
+		.filter_map(|entry| {
+			let entry = entry.trim();
+			let (start, end) = entry.split_once('-').unwrap_or((entry, entry));
+			Some((start.parse().ok()?, end.parse().ok()?))
+		})
+		.collect()
+}
+
+/// Merge `ranges` into the fewest ranges covering the same years, joining ranges that
+/// overlap or directly follow each other.
+fn merge_years(mut ranges: Vec<(i32, i32)>) -> Vec<(i32, i32)> {
+	ranges.sort();
+	let mut merged: Vec<(i32, i32)> = Vec::new();
+	for (start, end) in ranges {
+		match merged.last_mut() {
+			Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+			_ => merged.push((start, end)),
+		}
+	}
+	merged
+}
+
+/// Format `(start, end)` ranges as a comma-separated year list.
+fn format_years(ranges: &[(i32, i32)]) -> String {
+	ranges
+		.iter()
+		.map(|&(start, end)| {
+			if start == end {
+				start.to_string()
+			} else {
+				format!("{}-{}", start, end)
+			}
+		})
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Returns `years`, as taken from a header, extended to end in `current_year`, or
+/// `None` if it already does. Normally everything collapses into one range from the
+/// first year on; with `preserve_gaps`, a year that does not directly follow the last
+/// one is appended as a separate entry instead (`2018-2020` becomes `2018-2020, 2025`).
+/// A list that grows beyond `year_list_max` entries collapses into one range again, as
+/// does an existing list that is already longer.
+fn extend_years(
+	years: &str,
+	current_year: i32,
+	preserve_gaps: bool,
+	year_list_max: Option<usize>,
+) -> Option<String> {
+	let mut ranges = parse_years(years);
+	let (first_start, _) = *ranges.first()?;
+	let (_, last_end) = *ranges.last()?;
+	let too_long = |ranges: &Vec<(i32, i32)>| year_list_max.is_some_and(|max| ranges.len() > max);
+	if last_end == current_year {
+		return too_long(&ranges).then(|| format_years(&[(first_start, current_year)]));
+	}
+
+	if !preserve_gaps {
+		ranges = vec![(first_start, current_year)];
+	} else if last_end == current_year - 1 {
+		ranges.last_mut().unwrap().1 = current_year;
+	} else {
+		ranges.push((current_year, current_year));
+	}
+	if too_long(&ranges) {
+		ranges = vec![(first_start, current_year)];
+	}
+	Some(format_years(&ranges))
+}
+
+/// Returns the byte offset in `content` at which a new copyright header is inserted.
+///
+/// Scripts may open with a `#!` interpreter line, which has to stay first for the
+/// kernel to recognize it, so the header goes below it. In Rust, inner attributes
+/// (`#![...]`) and inner doc comments (`//!`) are a different matter: a plain
+/// `/* */` comment may precede them without changing their meaning, whereas moving the
+/// header below them would leave it between the crate docs and the code. The header is
+/// therefore placed above them, and since it never starts with `/**` or `/*!` it does
+/// not become part of the documentation.
+///
+/// A UTF-8 byte order mark always stays in front of everything else.
+fn header_insert_offset(file_path: &Path, content: &str) -> usize {
+	let bom_len = if starts_with_bom(content.as_bytes()) {
+		UTF8_BOM.len()
+	} else {
+		0
+	};
+	let body = &content[bom_len..];
+
+	let is_rust = file_path
+		.extension()
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("rs"));
+	if body.starts_with("#!") && !(is_rust && body.starts_with("#![")) {
+		return bom_len + body.find('\n').map_or(body.len(), |i| i + 1);
+	}
+	bom_len
+}
+
+/// The UTF-8 encoding of U+FEFF, which some editors write at the start of a file.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Check whether `bytes` start with a UTF-8 byte order mark.
+fn starts_with_bom(bytes: &[u8]) -> bool {
+	bytes.starts_with(UTF8_BOM)
+}
+
+/// Remove the UTF-8 byte order mark from each of `files` that has one, reporting
+/// the affected files. Nothing is written when `dry_run` is set.
+fn strip_boms(files: &[PathBuf], dry_run: bool) -> io::Result<()> {
+	for file in files {
+		let bytes = fs::read(file)?;
+		if !starts_with_bom(&bytes) {
+			continue;
+		}
+		if dry_run {
+			println!("Would remove BOM: {}", file.display());
+		} else {
+			fs::write(file, &bytes[UTF8_BOM.len()..])?;
+			println!("Removed BOM: {}", file.display());
+		}
+	}
+	Ok(())
+}
+
+/// Find the footer sections in `content`: comment blocks that open on their own line
+/// after a blank line and whose first line contains one of `labels` followed by a
+/// colon, e.g. `License:`. Each returned range
+/// spans from the newline before that blank line to the end of the closing
+/// delimiter. Block comments end at the first line consisting of `comment_end`; line
+/// comments (where start and end are the same marker) end at the last bare marker
+/// line among the consecutive commented lines. Nestable `(* *)` comments end where
+/// their nesting depth returns to zero.
+fn find_footer_blocks(
+	content: &str,
+	comment_start: &str,
+	comment_end: &str,
+	labels: &[&str],
+) -> Vec<Range<usize>> {
+	let markers: Vec<String> = labels.iter().map(|label| format!("{}:", label)).collect();
+	let start_marker = comment_start.trim();
+	let end_marker = comment_end.trim();
+	let mut lines = Vec::new();
+	let mut offset = 0;
+	for line in content.split('\n') {
+		lines.push((offset, line));
+		offset += line.len() + 1;
+	}
+
+	let mut blocks = Vec::new();
+	let mut i = 1;
+	while i + 1 < lines.len() {
+		let opens_footer = lines[i].1.trim() == start_marker
+			&& lines[i - 1].1.trim().is_empty()
+			&& markers
+				.iter()
+				.any(|marker| lines[i + 1].1.contains(marker.as_str()));
+		if !opens_footer {
+			i += 1;
+			continue;
+		}
+
+		let mut close = i + 2;
+		if nests(comment_start) {
+			let mut depth = 0;
+			close = i;
+			while close < lines.len() {
+				let line = lines[close].1;
+				depth += line.matches(start_marker).count() as isize;
+				depth -= line.matches(end_marker).count() as isize;
+				if depth <= 0 {
+					break;
+				}
+				close += 1;
+			}
+			if close == lines.len() {
+				break;
+			}
+		} else if start_marker == end_marker {
+			let mut last = i + 1;
+			while close < lines.len() && lines[close].1.trim_start().starts_with(start_marker) {
+				if lines[close].1.trim() == end_marker {
+					last = close;
+				}
+				close += 1;
+			}
+			close = last;
+		} else {
+			while close < lines.len() && lines[close].1.trim() != end_marker {
+				close += 1;
+			}
+			if close == lines.len() {
+				break;
+			}
+		}
+
+		let (close_offset, close_line) = lines[close];
+		let block_start = lines[i - 1].0.saturating_sub(1);
+		blocks.push(block_start..close_offset + close_line.trim_end().len());
+		i = close + 1;
+	}
+	blocks
+}
+
+/// The location of a copyright notice line in a file's header.
+struct NoticeLine {
+	/// The whole line, without its line break.
+	line: Range<usize>,
+	/// The notice word, e.g. `Copyright (c)`.
+	word: Range<usize>,
+	/// The years of the notice.
+	years: Range<usize>,
+	/// Where the holder's name begins.
+	holder: usize,
+}
+
+/// Find the first line of `content` with `author`'s copyright notice, ignoring the
+/// lines within the `footers` ranges, whose license text may well contain a notice of
+/// its own, and lines that are not comments in `style`: a notice must be on a line
+/// starting with the comment's opening delimiter or line prefix, or inside a block
+/// comment. The one exception is a JavaScript comment inside the `<script>` block of an
+/// HTML-style component, where another tool may have put the header. The holder must
+/// be `author` itself or start with it followed by punctuation or a space, as in
+/// `Jane Doe and contributors`, so that another holder whose name merely starts the
+/// same way is never mistaken for them.
+fn find_holder_line(
+	content: &str,
+	author: &str,
+	style: (&str, &str, &str),
+	footers: &[Range<usize>],
+) -> Option<NoticeLine> {
+	let (comment_start, comment_prefix, comment_end) = style;
+	let (start_marker, prefix_marker, end_marker) = (
+		comment_start.trim(),
+		comment_prefix.trim(),
+		comment_end.trim(),
+	);
+	let notice_regex = notice_regex();
+	let mut in_block = false;
+	let mut in_script = false;
+	let mut offset = 0;
+	for line in content.split_inclusive('\n') {
+		let mut start = offset;
+		offset += line.len();
+		let mut line = line.trim_end_matches(['\r', '\n']);
+		if start == 0 {
+			if let Some(rest) = line.strip_prefix('\u{FEFF}') {
+				start += '\u{FEFF}'.len_utf8();
+				line = rest;
+			}
+		}
+		let text = line.trim_start();
+
+		let is_comment = in_block
+			|| text.starts_with(start_marker)
+			|| (!prefix_marker.is_empty() && text.starts_with(prefix_marker))
+			|| (in_script
+				&& (text.starts_with("//") || text.starts_with("/*") || text.starts_with('*')));
+		if start_marker != end_marker {
+			let opened = if in_block {
+				text
+			} else {
+				text.strip_prefix(start_marker).unwrap_or_default()
+			};
+			in_block = (in_block || text.starts_with(start_marker)) && !opened.contains(end_marker);
+		}
+		if start_marker == "<!--" {
+			if text.starts_with("<script") {
+				in_script = true;
+			} else if text.starts_with("</script") {
+				in_script = false;
+			}
+		}
+		if !is_comment || footers.iter().any(|footer| footer.contains(&start)) {
+			continue;
+		}
+
+		let Some(caps) = notice_regex.captures(line) else {
+			continue;
+		};
+		let holder = caps[3].trim();
+		let holder = holder.strip_suffix(end_marker).unwrap_or(holder).trim_end();
+		if holder_is(holder, author) {
+			let word = caps.get(1).unwrap().range();
+			let years = caps.get(2).unwrap().range();
+			return Some(NoticeLine {
+				line: start..start + line.len(),
+				word: start + word.start..start + word.end,
+				years: start + years.start..start + years.end,
+				holder: start + caps.get(3).unwrap().start(),
+			});
+		}
+	}
+	None
+}
+
+/// Whether the notice `holder` names `author`: it is `author` itself or starts with it
+/// followed by punctuation or a space, as in `Jane Doe and contributors`.
+fn holder_is(holder: &str, author: &str) -> bool {
+	holder
+		.strip_prefix(author)
+		.is_some_and(|rest| rest.chars().next().is_none_or(|c| !c.is_alphanumeric()))
+}
+
+/// Holder orders accepted by `--holder-order`.
+const HOLDER_ORDERS: [&str; 3] = ["config", "alpha", "year"];
+
+/// Reorder the copyright lines around `author`'s notice, i.e. the run of adjacent
+/// notice lines it belongs to, by holder name (`alpha`) or by first year (`year`). The
+/// lines are only reordered if they are all written alike, so that no comment
+/// delimiter ends up on the wrong line. Returns `None` if nothing moves.
+fn order_holders(
+	content: &str,
+	author: &str,
+	style: (&str, &str, &str),
+	footers: &[Range<usize>],
+	order: &str,
+) -> Option<String> {
+	if order == "config" {
+		return None;
+	}
+	let (_, _, comment_end) = style;
+	let notice = find_holder_line(content, author, style, footers)?;
+	let notice_regex = Regex::new(&format!(
+		r"^(.*?)({})[ \t]*({}){}(.+)$",
+		NOTICE_WORD_PATTERN, YEARS_PATTERN, HOLDER_GAP_PATTERN
+	))
+	.unwrap();
+
+	struct HolderLine {
+		range: Range<usize>,
+		/// Everything before the notice word, such as ` * `.
+		prefix: String,
+		/// Whether the line ends with the comment's closing delimiter.
+		closes: bool,
+		/// The holder, lowercased for sorting.
+		holder: String,
+		first_year: i32,
+	}
+	let mut lines = Vec::new();
+	let mut offset = 0;
+	for line in content.split_inclusive('\n') {
+		let range = offset..offset + line.trim_end_matches(['\r', '\n']).len();
+		offset += line.len();
+		lines.push(notice_regex.captures(&content[range.clone()]).map(|caps| {
+			let holder = caps[4].trim();
+			let closes = holder.ends_with(comment_end.trim());
+			let holder = holder
+				.strip_suffix(comment_end.trim())
+				.unwrap_or(holder)
+				.trim_end();
+			let first_year = parse_years(&caps[3]).first().map_or(0, |(start, _)| *start);
+			HolderLine {
+				range,
+				prefix: caps[1].to_string(),
+				closes,
+				holder: holder.to_lowercase(),
+				first_year,
+			}
+		}));
+	}
+	let at = lines.iter().position(|line| {
+		line.as_ref()
+			.is_some_and(|line| line.range.start == notice.line.start)
+	})?;
+	let mut first = at;
+	while first > 0 && lines[first - 1].is_some() {
+		first -= 1;
+	}
+	let mut last = at;
+	while last + 1 < lines.len() && lines[last + 1].is_some() {
+		last += 1;
+	}
+	let block: Vec<_> = lines[first..=last].iter().flatten().collect();
+	if block
+		.iter()
+		.any(|line| line.prefix != block[0].prefix || line.closes != block[0].closes)
+	{
+		return None;
+	}
+
+	let mut sorted = block.clone();
+	if order == "alpha" {
+		sorted.sort_by(|a, b| a.holder.cmp(&b.holder));
+	} else {
+		sorted.sort_by_key(|line| line.first_year);
+	}
+	if sorted.iter().zip(&block).all(|(a, b)| a.range == b.range) {
+		return None;
+	}
+	let text: Vec<&str> = sorted
+		.iter()
+		.map(|line| &content[line.range.clone()])
+		.collect();
+	let mut reordered = content.to_string();
+	reordered.replace_range(
+		block[0].range.start..block[block.len() - 1].range.end,
+		&text.join("\n"),
+	);
+	Some(reordered)
+}
+
+/// Collapse repeated copyright notices for the same holder, as left behind by earlier
+/// runs that failed to recognize their own header, into the first of them, crediting
+/// the years of all of them. Both one-line headers and notice lines inside block
+/// comments count. Footers are not touched. Returns `None` if there are no duplicates.
+fn collapse_duplicate_headers(
+	content: &str,
+	comment_start: &str,
+	comment_end: &str,
+) -> Option<String> {
+	let notice_regex = notice_regex();
+	let footers = find_footer_blocks(content, comment_start, comment_end, &["License"]);
+
+	let (start_marker, end_marker) = (comment_start.trim(), comment_end.trim());
+	let mut headers: BTreeMap<String, Vec<NoticeLine>> = BTreeMap::new();
+	let mut in_block = false;
+	let mut offset = 0;
+	for line in content.split_inclusive('\n') {
+		let start = offset;
+		offset += line.len();
+		let text = line.trim();
+		let is_comment = in_block || text.starts_with(start_marker);
+		if start_marker != end_marker {
+			let opened = if in_block {
+				text
+			} else {
+				text.strip_prefix(start_marker).unwrap_or_default()
+			};
+			in_block = (in_block || text.starts_with(start_marker)) && !opened.contains(end_marker);
+		}
+		if !is_comment || footers.iter().any(|footer| footer.contains(&start)) {
+			continue;
+		}
+		let Some(caps) = notice_regex.captures(line) else {
+			continue;
+		};
+		let holder = caps[3].trim();
+		let holder = holder.strip_suffix(end_marker).unwrap_or(holder).trim_end();
+		let word = caps.get(1).unwrap().range();
+		let years = caps.get(2).unwrap().range();
+		headers
+			.entry(holder.to_string())
+			.or_default()
+			.push(NoticeLine {
+				line: start..start + line.trim_end_matches(['\r', '\n']).len(),
+				word: start + word.start..start + word.end,
+				years: start + years.start..start + years.end,
+				holder: start + caps.get(3).unwrap().start(),
+			});
+	}
+
+	// Edits as (range, replacement), applied back to front.
+	let mut edits = Vec::new();
+	for lines in headers.values().filter(|lines| lines.len() > 1) {
+		let ranges = lines
+			.iter()
+			.flat_map(|notice| parse_years(&content[notice.years.clone()]))
+			.collect();
+		edits.push((lines[0].years.clone(), format_years(&merge_years(ranges))));
+		for notice in &lines[1..] {
+			let mut lines_after = content[notice.line.end..].split_inclusive('\n');
+			let mut end = notice.line.end + lines_after.next().map_or(0, |rest| rest.len());
+			// A duplicate standing in its own paragraph takes the blank line after it along.
+			let own_paragraph = content[..notice.line.start].ends_with("\n\n")
+				|| content[..notice.line.start].ends_with("\n\r\n");
+			if let Some(blank) = lines_after.next().filter(|line| line.trim().is_empty()) {
+				if own_paragraph {
+					end += blank.len();
+				}
+			}
+			edits.push((notice.line.start..end, String::new()));
+		}
+	}
+	if edits.is_empty() {
+		return None;
+	}
+	edits.sort_by_key(|(range, _)| range.start);
+	let mut collapsed = content.to_string();
+	for (range, replacement) in edits.into_iter().rev() {
+		collapsed.replace_range(range, &replacement);
+	}
+	Some(collapsed)
+}
+
+/// Whether comments opened by `comment_start` nest, as ML-style `(* *)` comments do.
+fn nests(comment_start: &str) -> bool {
+	comment_start.trim() == "(*"
+}
+
+/// Prefix each line of `text` with `comment_prefix`, leaving only the bare marker on
+/// blank lines so they carry no trailing whitespace.
+fn comment_lines(text: &str, comment_prefix: &str) -> String {
+	text.lines()
+		.map(|line| {
+			if line.trim().is_empty() {
+				comment_prefix.trim_end().to_string()
+			} else {
+				format!("{}{}", comment_prefix, line)
+			}
+		})
+		.collect::<Vec<String>>()
+		.join("\n")
+}
+
+/// Check whether `path` is a Terraform or HCL file, which may use either `#` or `//`
+/// line comments.
+fn is_hcl_file(path: &Path) -> bool {
+	path.extension()
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("tf") || ext.eq_ignore_ascii_case("hcl"))
+}
+
+/// Names the encoding of `bytes`: `UTF-8` if they are valid UTF-8, `binary` if they
+/// contain NUL bytes, and otherwise the most likely legacy encoding.
+fn detect_encoding(bytes: &[u8]) -> &'static str {
+	if std::str::from_utf8(bytes).is_ok() {
+		return "UTF-8";
+	}
+	if bytes.contains(&0) {
+		return "binary";
+	}
+	let mut detector = chardetng::EncodingDetector::new(chardetng::Iso2022JpDetection::Allow);
+	detector.feed(bytes, true);
+	detector.guess(None, chardetng::Utf8Detection::Deny).name()
+}
+
+/// Whether the start of the file at `path` contains a NUL byte, which text files don't.
+fn looks_binary(path: &Path) -> io::Result<bool> {
+	let mut head = Vec::with_capacity(1024);
+	fs::File::open(path)?.take(1024).read_to_end(&mut head)?;
+	Ok(head.contains(&0))
+}
+
+/// Read the file at `file_path` as source text, or return why it should be skipped:
+/// it is over the size limit or under `min_file_size` bytes, is binary or not UTF-8,
+/// or is not source code despite its extension.
+fn read_source(file_path: &Path, min_file_size: u64) -> io::Result<Result<String, SkipReason>> {
+	// Skip very large files.
+	let metadata = fs::metadata(file_path)?;
+	if metadata.len() > 1_000_000 {
+		return Ok(Err(SkipReason::Large));
+	}
+	if metadata.len() < min_file_size {
+		return Ok(Err(SkipReason::Small));
+	}
+
+	// Sniff the first kilobyte for NUL bytes before reading the whole file, so binaries
+	// that pass the extension filter are rejected cheaply.
+	if looks_binary(file_path)? {
+		return Ok(Err(SkipReason::Binary));
+	}
+
+	// Read the file as text.
+	let Ok(content) = fs::read_to_string(file_path) else {
+		return Ok(Err(SkipReason::NotUtf8));
+	};
+
+	// `.mm` is Objective-C++, but also the extension of FreeMind mind maps, which are XML.
+	let is_mind_map = file_path
+		.extension()
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("mm"))
+		&& content
+			.trim_start_matches('\u{FEFF}')
+			.trim_start()
+			.starts_with('<');
+	if is_mind_map {
+		return Ok(Err(SkipReason::NotSource));
+	}
+	Ok(Ok(content))
+}
+
+/// Update a single file with the copyright header at the top and license footer
+/// at the bottom.
+fn update_file(file_path: &Path, options: &Options) -> io::Result<Outcome> {
+	let content = match read_source(file_path, options.min_file_size)? {
+		Ok(content) => content,
+		Err(reason) => {
+			let what = match reason {
+				SkipReason::Large => "large file",
+				SkipReason::Small => "small file",
+				SkipReason::Binary | SkipReason::NotUtf8 => "binary file",
+				SkipReason::NotSource => "mind map",
+				SkipReason::NoMapping => unreachable!("read_source does not parse YAML"),
+			};
+			options.note(format!("Skipping {}: {}", what, file_path.display()));
+			return Ok(Outcome::Skipped(reason));
+		}
+	};
+
+	let Some((updated_content, final_content)) = annotate(file_path, &content, options) else {
+		return Ok(Outcome::Skipped(SkipReason::NoMapping));
+	};
+
+	let changed = if options.stale_footers {
+		updated_content != final_content
+	} else {
+		content != final_content
+	};
+	if options.check && !is_structured(file_path, options) {
+		if let Some(header_regex) = &options.header_regex {
+			if !header_regex.is_match(top_block(&content)) {
+				options.note(format!("Header does not match: {}", file_path.display()));
+				return Ok(Outcome::Mismatch);
+			}
+		}
+		if options.no_duplicate_headers {
+			let (comment_start, _, comment_end) = get_comment_style(file_path);
+			if collapse_duplicate_headers(&content, comment_start, comment_end).is_some() {
+				options.note(format!("Duplicate headers: {}", file_path.display()));
+				return Ok(Outcome::Mismatch);
+			}
+		}
+		if let Some(max_line) = options.header_within_lines {
+			if notice_line_number(file_path, &content, options).is_none_or(|line| line > max_line) {
+				options.note(format!(
+					"Header not within the first {} lines: {}",
+					max_line,
+					file_path.display()
+				));
+				return Ok(Outcome::Mismatch);
+			}
+		}
+	}
+
+	report_update(
+		file_path,
+		&content,
+		&updated_content,
+		&final_content,
+		changed,
+		options,
+	)
+}
+
+/// The 1-based number of the line with the author's copyright notice in `content`,
+/// not counting notices in license footers.
+fn notice_line_number(file_path: &Path, content: &str, options: &Options) -> Option<usize> {
+	let style = get_comment_style(file_path);
+	let (comment_start, _, comment_end) = style;
+	let labels: Vec<&str> = std::iter::once("License")
+		.chain(
+			options
+				.footer_sections
+				.iter()
+				.map(|(label, _)| label.as_str()),
+		)
+		.collect();
+	let footers = find_footer_blocks(content, comment_start, comment_end, &labels);
+	let notice = find_holder_line(content, &options.author, style, &footers)?;
+	Some(content[..notice.line.start].matches('\n').count() + 1)
+}
+
+/// The temporary file next to `file_path` that `--two-pass` stages its content in.
+fn staged_path(file_path: &Path) -> PathBuf {
+	let mut name = std::ffi::OsString::from(".");
+	name.push(file_path.file_name().unwrap_or_default());
+	name.push(".copywriter-tmp");
+	file_path.with_file_name(name)
+}
+
+/// Write `content` to the staging file for `file_path`, with the same permissions.
+fn stage_write(file_path: &Path, content: &str) -> io::Result<()> {
+	let staged = staged_path(file_path);
+	fs::write(&staged, content)?;
+	fs::set_permissions(&staged, fs::metadata(file_path)?.permissions())
+}
+
+/// Remove the staging files of `files`, leaving the originals untouched.
+fn discard_staged(files: &[PathBuf]) {
+	for file in files {
+		let _ = fs::remove_file(staged_path(file));
+	}
+}
+
+/// The second pass of `--two-pass`: rename every staging file over its original. Each
+/// rename is atomic, so if one fails, the files before it are fully updated and the
+/// rest keep their previous content.
+fn commit_staged(files: &[PathBuf], options: &Options) -> io::Result<()> {
+	for (i, file) in files.iter().enumerate() {
+		if let Err(e) = fs::rename(staged_path(file), file) {
+			discard_staged(&files[i..]);
+			return Err(e);
+		}
+		options.note(format!("Updated: {}", file.display()));
+	}
+	Ok(())
+}
+
+/// Set every `SPDX-License-Identifier` tag in `content` to `spdx_id`, keeping whatever
+/// closes the comment after it. Returns `None` if no tag changes.
+fn set_spdx_id(content: &str, spdx_id: &str) -> Option<String> {
+	let tag_regex = Regex::new(
+		r"(?m)(SPDX-License-Identifier:[ \t]*)(.+?)([ \t]*(?:\*/|\*\)|-->|--\]\]|\})?[ \t]*\r?$)",
+	)
+	.unwrap();
+	let retagged = tag_regex.replace_all(content, |caps: &regex::Captures| {
+		format!("{}{}{}", &caps[1], spdx_id, &caps[3])
+	});
+	(retagged != content).then(|| retagged.into_owned())
+}
+
+/// Whether `file_path` gets structured metadata rather than comments.
+fn is_structured(file_path: &Path, options: &Options) -> bool {
+	let structured = match options.rules.resolve(file_path) {
+		Some(rule) => rule.structured.unwrap_or(options.structured_metadata),
+		None => options.structured_metadata && !is_workflow_file(file_path),
+	};
+	structured && metadata::Format::of(file_path).is_some()
+}
+
+/// Add or update the copyright header and license footer in `content`, styled for
+/// `file_path`. Returns the content with just the header updated and the final
+/// content, or `None` if the file has to be skipped.
+fn annotate(file_path: &Path, content: &str, options: &Options) -> Option<(String, String)> {
+	let author_name = options.author.as_str();
+	let license_content = options.license.as_str();
+	let current_year = options.now.year();
+	if let Some(format) =
+		metadata::Format::of(file_path).filter(|_| is_structured(file_path, options))
+	{
+		let Some(final_content) = metadata::annotate(
+			format,
+			content,
+			author_name,
+			current_year,
+			options.preserve_gaps,
+			options.year_list_max,
+		) else {
+			options.note(format!(
+				"Skipping file without a top-level mapping: {}",
+				file_path.display()
+			));
+			return None;
+		};
+		return Some((final_content.clone(), final_content));
+	}
+
+	let existing_style = if options.keep_existing_style {
+		existing_comment_style(content, author_name)
+	} else {
+		None
+	};
+	let rule = options.rules.resolve(file_path);
+	let (style, reason) = if let Some(style) = existing_style {
+		(style, "existing header")
+	} else if let Some((style, origin)) =
+		rule.and_then(|rule| Some((rule.comment_style?, rule.origin)))
+	{
+		(style, origin)
+	} else if options.hcl_slash_comments && is_hcl_file(file_path) {
+		(("//", "// ", "//"), "--hcl-comment")
+	} else if is_bazel_build_file(file_path) {
+		(get_comment_style(file_path), "file name")
+	} else if file_path.extension().is_some() {
+		(get_comment_style(file_path), "extension")
+	} else if let Some(style) = shebang_comment_style(content, &options.rules) {
+		(style, "shebang")
+	} else {
+		(get_comment_style(file_path), "default")
+	};
+	if options.verbose {
+		options.note(format!(
+			"Comment style for {}: {:?} from {}",
+			file_path.display(),
+			style,
+			reason
+		));
+	}
+	let (comment_start, comment_prefix, comment_end) = style;
 
-	// Create a regex to match an existing copyright header.
-	let copyright_pattern = format!(
-		r"{}\s*Copyright \(c\) (\d{{4}}(?:-\d{{4}})?)(?: {}\s*.*?){}",
-		regex::escape(comment_start),
-		regex::escape(author_name),
-		regex::escape(comment_end)
-	);
-	let copyright_regex = Regex::new(&copyright_pattern).unwrap();
-
-	let updated_content = if let Some(caps) = copyright_regex.captures(&content) {
-		let year_str = caps.get(1).unwrap().as_str();
-		if year_str.contains('-') {
-			let parts: Vec<&str> = year_str.split('-').collect();
-			let start_year: i32 = parts[0].parse().unwrap();
-			let end_year: i32 = parts[1].parse().unwrap();
-			if end_year == current_year {
-				content.clone()
-			} else {
+	let labels: Vec<&str> = std::iter::once("License")
+		.chain(
+			options
+				.footer_sections
+				.iter()
+				.map(|(label, _)| label.as_str()),
+		)
+		.collect();
+
+	// A footer followed by code gets cut out here and re-appended at the end below.
+	let mut source = content.to_string();
+	if options.repair_footer {
+		let footers = find_footer_blocks(content, comment_start, comment_end, &labels);
+		let misplaced: Vec<_> = footers
+			.into_iter()
+			.filter(|footer| !content[footer.end..].trim().is_empty())
+			.collect();
+		if !misplaced.is_empty() {
+			options.note(format!(
+				"Moving misplaced footer to end of file: {}",
+				file_path.display()
+			));
+		}
+		for footer in misplaced.into_iter().rev() {
+			source.replace_range(footer, "");
+		}
+	}
+
+	// Look for the author's own notice outside the footers. If the line is a
+	// complete one-line header it is rewritten in canonical form; otherwise (a line of
+	// a multi-line header, or a header whose style is kept) only its years change, so
+	// other holders' lines stay byte-identical.
+	let footers = find_footer_blocks(&source, comment_start, comment_end, &labels);
+	let notice = find_holder_line(&source, author_name, style, &footers);
+	let mut updated_content = if let Some(notice) = notice {
+		let line = &source[notice.line.clone()];
+		let years_str = &source[notice.years.clone()];
+		let word = match options.copyright_word {
+			Some(word) => word,
+			None if options.normalize_punctuation => "Copyright (c)",
+			None => &source[notice.word.clone()],
+		};
+		let word_changed = word != &source[notice.word.clone()];
+		// Single spaces around the years, and no comma after them.
+		let spacing_changed = options.normalize_punctuation
+			&& (notice.years.start != notice.word.end + 1
+				|| &source[notice.years.end..notice.holder] != " ");
+		let indent = line.len() - line.trim_start().len();
+		let whole_header = line.trim().starts_with(comment_start.trim())
+			&& line.trim().ends_with(comment_end.trim());
+		match extend_years(
+			years_str,
+			current_year,
+			options.preserve_gaps,
+			options.year_list_max,
+		) {
+			None if !word_changed
+				&& !spacing_changed
+				&& (!options.normalize_prefix || !whole_header) =>
+			{
+				source.clone()
+			}
+			// An ML header may carry nested comments after the holder, which a
+			// canonical rewrite would drop.
+			years if whole_header && existing_style.is_none() && !nests(comment_start) => {
 				let new_copyright = format!(
-					"{} Copyright (c) {}-{} {} {}",
-					comment_start, start_year, current_year, author_name, comment_end
+					"{} {} {} {} {}",
+					comment_start,
+					word,
+					years.as_deref().unwrap_or(years_str),
+					author_name,
+					comment_end
 				);
-				copyright_regex.replace(&content, new_copyright).to_string()
+				let header_start = notice.line.start + indent;
+				let header_end = notice.line.start + line.trim_end().len();
+				let mut updated = source.clone();
+				updated.replace_range(header_start..header_end, &new_copyright);
+				updated
 			}
-		} else {
-			let year: i32 = year_str.parse().unwrap();
-			if year == current_year {
-				content.clone()
-			} else {
-				let new_copyright = format!(
-					"{} Copyright (c) {}-{} {} {}",
-					comment_start, year, current_year, author_name, comment_end
+			years if spacing_changed => {
+				let mut updated = source.clone();
+				let years = years.as_deref().unwrap_or(years_str);
+				updated.replace_range(
+					notice.word.start..notice.holder,
+					&format!("{} {} ", word, years),
 				);
-				copyright_regex.replace(&content, new_copyright).to_string()
+				updated
+			}
+			years => {
+				let mut updated = source.clone();
+				if let Some(years) = years {
+					updated.replace_range(notice.years, &years);
+				}
+				updated.replace_range(notice.word, word);
+				updated
 			}
 		}
+	} else if let Some(block) = find_license_block(&source, comment_start, comment_end, &footers)
+		.filter(|_| options.merge_license_block)
+	{
+		// The notice goes on the line after a block comment's opening line, or first
+		// in a run of line comments. A one-line block comment gets one of its own above.
+		let first_line = source[block.clone()].lines().next().unwrap_or_default();
+		let indent = &first_line[..first_line.len() - first_line.trim_start().len()];
+		let notice = format!(
+			"{} {} {}",
+			options.copyright_word.unwrap_or("Copyright (c)"),
+			current_year,
+			author_name
+		);
+		let mut updated = source.clone();
+		if comment_start == comment_end {
+			updated.insert_str(
+				block.start,
+				&format!("{}{}{}\n", indent, comment_prefix, notice),
+			);
+		} else if block.len() == first_line.len() {
+			updated.insert_str(
+				block.start,
+				&format!("{}{} {} {}\n", indent, comment_start, notice, comment_end),
+			);
+		} else {
+			updated.insert_str(
+				block.start + first_line.len(),
+				&format!("\n{}{}{}", indent, comment_prefix, notice),
+			);
+		}
+		updated
 	} else {
+		let (preamble, rest) = source.split_at(header_insert_offset(file_path, &source));
+		// A shebang line is kept apart from the header by a blank line.
+		let separator = if preamble.trim_start_matches('\u{FEFF}').is_empty() {
+			""
+		} else if preamble.ends_with('\n') {
+			"\n"
+		} else {
+			"\n\n"
+		};
 		format!(
-			"{} Copyright (c) {} {} {}\n\n{}",
-			comment_start, current_year, author_name, comment_end, content
+			"{}{}{}{} {} {} {} {}\n\n{}",
+			preamble,
+			separator,
+			options.indent,
+			comment_start,
+			options.copyright_word.unwrap_or("Copyright (c)"),
+			current_year,
+			author_name,
+			comment_end,
+			rest
 		)
 	};
 
-	// Format the license text using the file's comment style.
-	let formatted_license = license_content
-		.lines()
-		.map(|line| {
-			if line.trim().is_empty() {
-				comment_prefix.trim_end().to_string()
-			} else {
-				format!("{}{}", comment_prefix, line)
+	let footers = find_footer_blocks(&updated_content, comment_start, comment_end, &labels);
+	if let Some(reordered) = order_holders(
+		&updated_content,
+		author_name,
+		style,
+		&footers,
+		&options.holder_order,
+	) {
+		updated_content = reordered;
+	}
+
+	if let Some(spdx_id) = &options.spdx_id {
+		if let Some(retagged) = set_spdx_id(&updated_content, spdx_id) {
+			updated_content = retagged;
+		}
+	}
+
+	if let Some(trailer) = &options.header_trailer {
+		let trailer_line = format!(
+			"{}{} {} {}",
+			options.indent, comment_start, trailer, comment_end
+		);
+		let footers = find_footer_blocks(&updated_content, comment_start, comment_end, &labels);
+		if let Some(notice) = find_holder_line(&updated_content, author_name, style, &footers) {
+			// A multi-line block header ends at the line that closes the comment.
+			let mut header_end = notice.line.end;
+			if comment_start.trim() != comment_end.trim()
+				&& !updated_content[notice.line.clone()]
+					.trim_end()
+					.ends_with(comment_end.trim())
+			{
+				let rest = &updated_content[header_end..];
+				if let Some(close) = rest.find(comment_end.trim()) {
+					header_end += close + comment_end.trim().len();
+				}
 			}
-		})
-		.collect::<Vec<String>>()
-		.join("\n");
+			let next_line = updated_content[header_end..]
+				.trim_start_matches(['\r', '\n'])
+				.lines()
+				.next();
+			let has_trailer = updated_content[header_end..].starts_with(['\r', '\n'])
+				&& next_line.is_some_and(|line| line.trim() == trailer_line.trim());
+			if !has_trailer {
+				updated_content.insert_str(header_end, &format!("\n{}", trailer_line));
+			}
+		}
+	}
 
-	let license_footer = format!(
-		"\n\n{}\n{}License:\n{}\n{}",
-		comment_start, comment_prefix, formatted_license, comment_end
-	);
+	// Format the license text using the file's comment style.
+	let formatted_license = comment_lines(license_content, comment_prefix);
 
-	// Use a dot-all regex that matches:
-	// - Two newlines
-	// - The comment-start line
-	// - Some intervening lines (including one that contains "License:")
-	// - And ending with the comment-end at the end of the file.
-	let license_pattern = format!(
-		r"(?s)\n\n{}\n.*?License:.*?\n.*?{}\s*$",
-		regex::escape(comment_start),
-		regex::escape(comment_end)
+	// The whole footer is regenerated on every run, so the timestamp line is
+	// rewritten in place rather than accumulating.
+	let timestamp = if options.timestamp {
+		format!(
+			"\n{}\n{}Last updated: {}",
+			comment_prefix.trim_end(),
+			comment_prefix,
+			options.now.format("%Y-%m-%d")
+		)
+	} else {
+		String::new()
+	};
+	let mut license_footer = format!(
+		"\n\n{}\n{}License:\n{}{}\n{}",
+		comment_start, comment_prefix, formatted_license, timestamp, comment_end
 	);
-	let license_regex = Regex::new(&license_pattern).unwrap();
+	for (label, template) in &options.footer_sections {
+		let text = template
+			.replace("{author}", author_name)
+			.replace("{year}", &current_year.to_string());
+		license_footer.push_str(&format!(
+			"\n\n{}\n{}{}:\n{}\n{}",
+			comment_start,
+			comment_prefix,
+			label,
+			comment_lines(&text, comment_prefix),
+			comment_end
+		));
+	}
 
-	let final_content = if license_regex.is_match(&updated_content) {
-		// Replace the identified license footer with our new footer.
-		license_regex
-			.replace(&updated_content, license_footer.as_str())
-			.to_string()
-	} else {
+	if !options.indent.is_empty() {
+		license_footer = license_footer
+			.split('\n')
+			.map(|line| {
+				if line.is_empty() {
+					line.to_string()
+				} else {
+					format!("{}{}", options.indent, line)
+				}
+			})
+			.collect::<Vec<String>>()
+			.join("\n");
+	}
+
+	// The EOF comment follows the footer, so it is set aside while the footer is
+	// located and put back afterwards.
+	let body = match options.eof_comment.as_deref() {
+		Some(eof_comment) => match updated_content.trim_end().strip_suffix(eof_comment) {
+			Some(rest) if rest.is_empty() || rest.ends_with('\n') => rest.trim_end(),
+			_ => updated_content.as_str(),
+		},
+		None => updated_content.as_str(),
+	};
+
+	// Every section at the end of the file is regenerated, so sections can be added,
+	// edited, or reordered without leaving stale copies behind.
+	let mut footer_start = None;
+	loop {
+		let kept = &body[..footer_start.unwrap_or(body.len())];
+		match find_footer_blocks(kept, comment_start, comment_end, &labels).last() {
+			Some(footer) if kept[footer.end..].trim().is_empty() => {
+				footer_start = Some(footer.start)
+			}
+			_ => break,
+		}
+	}
+	let mut final_content = match footer_start {
+		// The file's own license block stands in for the footer.
+		None if options.merge_license_block
+			&& find_license_block(
+				body,
+				comment_start,
+				comment_end,
+				&find_footer_blocks(body, comment_start, comment_end, &labels),
+			)
+			.is_some() =>
+		{
+			body.to_string()
+		}
+		// The file's rule leaves the footer out.
+		_ if rule.is_some_and(|rule| !rule.footer) => body.to_string(),
+		// Another file in this directory carries the footer.
+		_ if options
+			.footer_files
+			.as_ref()
+			.is_some_and(|files| !files.contains(file_path)) =>
+		{
+			body.to_string()
+		}
+		// Replace the footer sections at the end of the file with our new footer.
+		Some(start) => format!("{}{}", &body[..start], license_footer),
 		// No license footer found; append the new footer.
-		format!("{}{}", updated_content.trim_end(), license_footer)
+		_ => format!("{}{}", body.trim_end(), license_footer),
+	};
+	if let Some(eof_comment) = &options.eof_comment {
+		final_content = format!("{}\n{}", final_content.trim_end(), eof_comment);
+	}
+
+	Some((updated_content, final_content))
+}
+
+/// Report the update of `file_path` from `content` to `final_content` and, outside of
+/// check and dry-run mode, write it. `header_content` is `content` with only the header
+/// updated, which is where `--diff-scope` splits the diff.
+fn report_update(
+	file_path: &Path,
+	content: &str,
+	header_content: &str,
+	final_content: &str,
+	changed: bool,
+	options: &Options,
+) -> io::Result<Outcome> {
+	let outcome = if changed {
+		Outcome::Changed
+	} else {
+		Outcome::Unchanged
 	};
 
-	if dry_run {
-		println!("Would update: {}", file_path.display());
-		if content != final_content {
-			println!("  Changes would be made.");
+	if options.check {
+		if outcome == Outcome::Changed && options.stale_footers {
+			options.note(format!("Stale footer: {}", file_path.display()));
+		} else if outcome == Outcome::Changed {
+			options.note(format!("Needs update: {}", file_path.display()));
+		}
+	} else if options.dry_run {
+		options.note(format!("Would update: {}", file_path.display()));
+		if outcome == Outcome::Changed && options.diff {
+			let (old, new) = match options.diff_scope.as_str() {
+				"header" => (content, header_content),
+				"footer" => (header_content, final_content),
+				_ => (content, final_content),
+			};
+			if old == new {
+				options.note(format!("  No {} changes.", options.diff_scope));
+			} else {
+				options.note(unified_diff(file_path, old, new, options.insertion_context));
+			}
+		} else if outcome == Outcome::Changed {
+			options.note("  Changes would be made.".to_string());
 		} else {
-			println!("  No changes needed.");
+			options.note("  No changes needed.".to_string());
+		}
+	} else if outcome == Outcome::Changed {
+		if let Some(staged) = &options.staged {
+			stage_write(file_path, final_content)?;
+			staged.borrow_mut().push(file_path.to_path_buf());
+			return Ok(outcome);
 		}
-	} else if content != final_content {
 		fs::write(file_path, final_content)?;
-		println!("Updated: {}", file_path.display());
+		options.note(format!("Updated: {}", file_path.display()));
 	} else {
-		println!("No changes needed: {}", file_path.display());
+		options.note(format!("No changes needed: {}", file_path.display()));
 	}
 
-	Ok(())
+	Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::TimeZone;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	/// A fresh directory under the system temp dir, removed when dropped.
+	pub(crate) struct TempDir(PathBuf);
+
+	impl TempDir {
+		pub(crate) fn new() -> TempDir {
+			static COUNT: AtomicUsize = AtomicUsize::new(0);
+			let dir = std::env::temp_dir().join(format!(
+				"copywriter-unit-{}-{}",
+				std::process::id(),
+				COUNT.fetch_add(1, Ordering::Relaxed)
+			));
+			fs::create_dir_all(&dir).unwrap();
+			TempDir(dir)
+		}
+
+		pub(crate) fn path(&self) -> &Path {
+			&self.0
+		}
+
+		/// Write `content` to `name` below the directory, creating parent directories.
+		pub(crate) fn write(&self, name: &str, content: &str) -> PathBuf {
+			let path = self.0.join(name);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(&path, content).unwrap();
+			path
+		}
+	}
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	/// Options as a plain run for `author` in 2026 would have them, with the license `MIT`.
+	fn options(author: &str) -> Options {
+		Options {
+			author: author.to_string(),
+			license: "MIT".to_string(),
+			now: Utc.with_ymd_and_hms(2026, 6, 1, 0, 0, 0).unwrap(),
+			dry_run: false,
+			diff: false,
+			diff_scope: "both".to_string(),
+			insertion_context: 0,
+			check: false,
+			stale_footers: false,
+			header_regex: None,
+			spdx_id: None,
+			no_duplicate_headers: false,
+			header_within_lines: None,
+			min_file_size: 0,
+			repair_footer: false,
+			merge_license_block: false,
+			timestamp: false,
+			normalize_prefix: false,
+			normalize_punctuation: false,
+			keep_existing_style: false,
+			preserve_gaps: false,
+			year_list_max: None,
+			hcl_slash_comments: false,
+			structured_metadata: false,
+			rules: Rules::default(),
+			eof_comment: None,
+			copyright_word: None,
+			header_trailer: None,
+			holder_order: "config".to_string(),
+			indent: String::new(),
+			footer_sections: Vec::new(),
+			staged: None,
+			footer_files: None,
+			format: "text".to_string(),
+			verbose: false,
+			quiet: true,
+		}
+	}
+
+	/// The final content `annotate` gives `content` as the file `name`.
+	fn annotated(name: &str, content: &str, options: &Options) -> String {
+		annotate(Path::new(name), content, options).unwrap().1
+	}
+
+	#[test]
+	fn license_names_are_searched_in_order_and_upwards() {
+		let dir = TempDir::new();
+		let license = dir.write("LICENSE", "MIT");
+		let copying = dir.write("COPYING", "GPL");
+		let file = dir.write("src/deep/main.rs", "fn main() {}\n");
+		assert_eq!(find_license(&file, &["COPYING", "LICENSE"]), Some(copying));
+		assert_eq!(find_license(&file, &["LICENCE", "LICENSE"]), Some(license));
+		assert_eq!(find_license(&file, &["LICENCE"]), None);
+	}
+
+	#[test]
+	fn rust_header_goes_above_inner_attributes_as_a_plain_comment() {
+		let source = "#![allow(dead_code)]\n//! Module docs.\n\nfn main() {}\n";
+		let content = annotated("lib.rs", source, &options("Jane Doe"));
+		assert!(content.starts_with("/* Copyright (c) 2026 Jane Doe  */\n"));
+		assert!(content.contains("\n#![allow(dead_code)]\n//! Module docs.\n"));
+		assert!(!content.starts_with("/**") && !content.starts_with("/*!"));
+		assert_eq!(annotated("lib.rs", &content, &options("Jane Doe")), content);
+	}
+
+	#[test]
+	fn ml_languages_get_block_comment_headers() {
+		let options = options("Jane Doe");
+		for name in ["main.ml", "sig.mli", "Program.fs", "script.fsx"] {
+			let content = annotated(name, "let x = 1\n", &options);
+			assert!(
+				content.starts_with("(* Copyright (c) 2026 Jane Doe  *)\n"),
+				"{}: {}",
+				name,
+				content
+			);
+			assert!(
+				content.contains("(*\n * License:\n * MIT\n *)"),
+				"{}",
+				content
+			);
+		}
+		assert!(annotated("main.re", "let x = 1;\n", &options).starts_with("/* Copyright"));
+	}
+
+	#[test]
+	fn header_regex_classifies_files_in_check_mode() {
+		let dir = TempDir::new();
+		let mut options = options("Jane Doe");
+		let good = dir.write("good.rs", &annotated("good.rs", "fn a() {}\n", &options));
+		let bad = dir.write("bad.rs", "/* (c) Someone Else */\n\nfn b() {}\n");
+		options.check = true;
+		options.header_regex = Some(
+			expand_header_regex(r"^/\* Copyright \(c\) {year} {author}", "Jane Doe", 2026).unwrap(),
+		);
+		assert!(update_file(&good, &options).unwrap() == Outcome::Unchanged);
+		assert!(update_file(&bad, &options).unwrap() == Outcome::Mismatch);
+		assert!(check_failure(Outcome::Mismatch, &options).is_some());
+	}
+
+	#[test]
+	fn repair_moves_a_misplaced_footer_to_the_end() {
+		let mut options = options("Jane Doe");
+		let start = annotated("main.rs", "fn a() {}\n", &options);
+		let footer = "/*\n * License:\n * MIT\n */";
+		let broken = start.replace(footer, "") + footer + "\n\nfn stray() {}\n";
+		options.repair_footer = true;
+		let repaired = annotated("main.rs", &broken, &options);
+		assert!(repaired.trim_end().ends_with(footer), "{}", repaired);
+		assert_eq!(repaired.matches("License:").count(), 1);
+		assert!(repaired.contains("fn stray() {}"));
+	}
+
+	#[test]
+	fn timestamp_line_is_updated_in_place() {
+		let mut options = options("Jane Doe");
+		options.timestamp = true;
+		let first = annotated("main.rs", "fn a() {}\n", &options);
+		assert!(first.contains(" * Last updated: 2026-06-01\n"), "{}", first);
+		options.now = Utc.with_ymd_and_hms(2026, 9, 15, 0, 0, 0).unwrap();
+		let second = annotated("main.rs", &first, &options);
+		assert!(
+			second.contains(" * Last updated: 2026-09-15\n"),
+			"{}",
+			second
+		);
+		assert_eq!(second.matches("Last updated:").count(), 1);
+		assert_eq!(second.matches("License:").count(), 1);
+	}
+
+	#[test]
+	fn holders_report_formats_carry_the_same_rows() {
+		let latest_years = BTreeMap::from([
+			("Acme, Inc.".to_string(), 2026),
+			("Old Corp".to_string(), 2019),
+		]);
+		let csv = render_holders_report(&latest_years, 2026, "csv");
+		assert_eq!(
+			csv,
+			"holder,latest_year,stale\n\"Acme, Inc.\",2026,false\nOld Corp,2019,true\n"
+		);
+		let markdown = render_holders_report(&latest_years, 2026, "markdown");
+		assert_eq!(
+			markdown,
+			"| Holder | Latest year | Stale |\n| --- | --- | --- |\n\
+			 | Acme, Inc. | 2026 | no |\n| Old Corp | 2019 | yes |\n"
+		);
+	}
+
+	#[test]
+	fn elixir_and_erlang_get_line_comment_headers() {
+		let options = options("Jane Doe");
+		let elixir = annotated("lib.ex", "defmodule A do\nend\n", &options);
+		assert!(
+			elixir.starts_with("# Copyright (c) 2026 Jane Doe #\n"),
+			"{}",
+			elixir
+		);
+		assert!(elixir.contains("#\n# License:\n# MIT\n#"), "{}", elixir);
+		let erlang = annotated("a.erl", "-module(a).\n", &options);
+		assert!(
+			erlang.starts_with("% Copyright (c) 2026 Jane Doe %\n"),
+			"{}",
+			erlang
+		);
+		assert!(erlang.contains("%\n% License:\n% MIT\n%"), "{}", erlang);
+		assert!(is_source_file(Path::new("test.exs")) && is_source_file(Path::new("a.hrl")));
+	}
+
+	#[test]
+	fn strip_bom_removes_only_the_bom() {
+		let dir = TempDir::new();
+		let with_bom = dir.write("a.rs", "\u{feff}fn a() {}\n// ünïcode\n");
+		let without = dir.write("b.rs", "fn b() {}\n");
+		let files = [with_bom.clone(), without.clone()];
+		strip_boms(&files, true).unwrap();
+		assert!(fs::read(&with_bom).unwrap().starts_with(UTF8_BOM));
+		strip_boms(&files, false).unwrap();
+		assert_eq!(
+			fs::read_to_string(&with_bom).unwrap(),
+			"fn a() {}\n// ünïcode\n"
+		);
+		assert_eq!(fs::read_to_string(&without).unwrap(), "fn b() {}\n");
+	}
+
+	#[test]
+	fn mis_prefixed_footer_and_header_are_normalized() {
+		let source =
+			"/*   Copyright (c) 2026 Jane Doe */\n\nfn a() {}\n\n/*\n* License:\n* MIT\n*/\n";
+		let mut options = options("Jane Doe");
+		let footer_only = annotated("main.rs", source, &options);
+		assert!(
+			footer_only.ends_with("\n/*\n * License:\n * MIT\n */"),
+			"{}",
+			footer_only
+		);
+		assert!(!footer_only.contains("\n* "));
+		assert!(footer_only.starts_with("/*   Copyright"));
+		options.normalize_prefix = true;
+		let normalized = annotated("main.rs", source, &options);
+		assert!(
+			normalized.starts_with("/* Copyright (c) 2026 Jane Doe  */\n"),
+			"{}",
+			normalized
+		);
+		assert_eq!(normalized.matches("License:").count(), 1);
+	}
+
+	#[test]
+	fn junit_report_has_a_case_per_file_and_failures_for_bad_ones() {
+		let mut options = options("Jane Doe");
+		options.check = true;
+		let results = [
+			(PathBuf::from("good.rs"), Outcome::Unchanged),
+			(PathBuf::from("a&b.rs"), Outcome::Changed),
+			(PathBuf::from("big.rs"), Outcome::Skipped(SkipReason::Large)),
+		];
+		let xml = render_junit(&results, &options);
+		assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+		assert!(xml.contains(
+			"<testsuite name=\"copywriter\" tests=\"3\" failures=\"1\" skipped=\"1\">\n"
+		));
+		assert!(xml.contains("<testcase classname=\"copywriter\" name=\"good.rs\"/>\n"));
+		assert!(xml.contains(
+			"<testcase classname=\"copywriter\" name=\"a&amp;b.rs\">\n    \
+			 <failure message=\"Copyright header or license footer needs updating\"/>\n  \
+			 </testcase>\n"
+		));
+		assert!(xml.contains("name=\"big.rs\">\n    <skipped/>\n"));
+		assert_eq!(xml.matches("<testcase ").count(), 3);
+		assert_eq!(xml.matches("<failure ").count(), 1);
+		assert!(xml.ends_with("</testsuite>\n"));
+	}
+
+	#[test]
+	fn gaps_are_preserved_only_on_request() {
+		assert_eq!(
+			extend_years("2018-2020", 2025, false, None).as_deref(),
+			Some("2018-2025")
+		);
+		assert_eq!(
+			extend_years("2018-2020", 2025, true, None).as_deref(),
+			Some("2018-2020, 2025")
+		);
+		assert_eq!(
+			extend_years("2018-2020", 2021, true, None).as_deref(),
+			Some("2018-2021")
+		);
+		assert_eq!(extend_years("2018-2020, 2025", 2025, true, None), None);
+
+		let mut options = options("Jane Doe");
+		options.preserve_gaps = true;
+		let source = "/* Copyright (c) 2018-2020 Jane Doe  */\n\nfn a() {}\n";
+		let content = annotated("main.rs", source, &options);
+		assert!(content.starts_with("/* Copyright (c) 2018-2020, 2026 Jane Doe  */\n"));
+	}
+
+	#[test]
+	fn r_and_julia_get_hash_headers() {
+		let options = options("Jane Doe");
+		for name in ["analysis.R", "script.r", "model.jl"] {
+			assert!(is_source_file(Path::new(name)), "{}", name);
+			let content = annotated(name, "x <- 1\n", &options);
+			assert!(
+				content.starts_with("# Copyright (c) 2026 Jane Doe #\n"),
+				"{}: {}",
+				name,
+				content
+			);
+			assert!(content.contains("#\n# License:\n# MIT\n#"), "{}", content);
+		}
+		assert!(!is_source_file(Path::new("report.Rmd")));
+	}
+
+	#[test]
+	fn long_license_footer_is_truncated_with_a_note() {
+		let license: String = (1..=40).map(|i| format!("GPL line {}\n", i)).collect();
+		let truncated = truncate_license(&license, 3, "COPYING");
+		assert_eq!(
+			truncated,
+			"GPL line 1\nGPL line 2\nGPL line 3\n\nSee COPYING for the full license text."
+		);
+		assert_eq!(truncate_license("Short\n", 3, "COPYING"), "Short\n");
+
+		let mut options = options("Jane Doe");
+		options.license = truncated;
+		let content = annotated("main.rs", "fn a() {}\n", &options);
+		assert!(content.contains(
+			" * License:\n * GPL line 1\n * GPL line 2\n * GPL line 3\n *\n \
+			 * See COPYING for the full license text.\n */"
+		));
+		assert!(!content.contains("GPL line 4"));
+		assert_eq!(annotated("main.rs", &content, &options), content);
+	}
+
+	#[test]
+	fn terraform_header_goes_above_the_terraform_block() {
+		let source = "terraform {\n  required_version = \">= 1.0\"\n}\n";
+		let mut options = options("Jane Doe");
+		let content = annotated("main.tf", source, &options);
+		assert!(
+			content.starts_with("# Copyright (c) 2026 Jane Doe #\n\nterraform {\n"),
+			"{}",
+			content
+		);
+		assert!(content.contains("#\n# License:\n# MIT\n#"));
+		options.hcl_slash_comments = true;
+		let content = annotated("config.hcl", source, &options);
+		assert!(
+			content.starts_with("// Copyright (c) 2026 Jane Doe //\n\nterraform {\n"),
+			"{}",
+			content
+		);
+	}
+
+	#[test]
+	fn insertion_context_widens_only_insertion_hunks() {
+		let old: String = (1..=30).map(|i| format!("import m{}\n", i)).collect();
+		let new = old.replace(
+			"import m15\n",
+			"import m15\n# Copyright (c) 2026 Jane Doe #\n",
+		);
+		let diff = unified_diff(Path::new("a.py"), &old, &new, 0);
+		assert!(
+			diff.contains("\n@@ -13,6 +13,7 @@\n import m13\n"),
+			"{}",
+			diff
+		);
+		let diff = unified_diff(Path::new("a.py"), &old, &new, 5);
+		assert!(
+			diff.contains("\n@@ -8,16 +8,17 @@\n import m8\n"),
+			"{}",
+			diff
+		);
+		assert!(diff.contains("+# Copyright (c) 2026 Jane Doe #\n import m16\n"));
+		assert!(diff.ends_with(" import m23"));
+
+		let changed = old.replace("import m15\n", "import n15\n");
+		let diff = unified_diff(Path::new("a.py"), &old, &changed, 5);
+		assert!(diff.contains("\n@@ -12,7 +12,7 @@\n"), "{}", diff);
+	}
+
+	#[test]
+	fn existing_line_comment_style_is_kept_on_request() {
+		let source = "// Copyright (c) 2024 Jane Doe\n\nfn a() {}\n";
+		let mut options = options("Jane Doe");
+		options.keep_existing_style = true;
+		let content = annotated("main.rs", source, &options);
+		assert!(
+			content.starts_with("// Copyright (c) 2024-2026 Jane Doe\n"),
+			"{}",
+			content
+		);
+		assert!(!content.contains("/*"), "{}", content);
+		assert!(
+			content.contains("//\n// License:\n// MIT\n//"),
+			"{}",
+			content
+		);
+		assert_eq!(annotated("main.rs", &content, &options), content);
+	}
+
+	#[test]
+	fn files_with_nul_bytes_are_skipped_as_binary() {
+		let dir = TempDir::new();
+		// Valid UTF-8 after the NUL, so only the sniff can tell it is not text.
+		let blob = dir.write("blob.json", &format!("{{\0\0{}", "x".repeat(500_000)));
+		let text = dir.write("data.json", "{\"a\": 1}\n");
+		assert!(looks_binary(&blob).unwrap());
+		assert!(!looks_binary(&text).unwrap());
+		assert!(read_source(&blob, 0).unwrap() == Err(SkipReason::Binary));
+		let before = fs::read(&blob).unwrap();
+		let outcome = update_file(&blob, &options("Jane Doe")).unwrap();
+		assert!(outcome == Outcome::Skipped(SkipReason::Binary));
+		assert_eq!(fs::read(&blob).unwrap(), before);
+	}
+
+	#[test]
+	fn visual_basic_and_pascal_headers() {
+		let options = options("Jane Doe");
+		let vb = annotated("Module1.vb", "Module Module1\nEnd Module\n", &options);
+		assert!(
+			vb.starts_with("' Copyright (c) 2026 Jane Doe '\n"),
+			"{}",
+			vb
+		);
+		assert!(vb.contains("'\n' License:\n' MIT\n'"), "{}", vb);
+		for name in ["unit1.pas", "unit1.pp"] {
+			let pascal = annotated(name, "unit Unit1;\nend.\n", &options);
+			assert!(
+				pascal.starts_with("{ Copyright (c) 2026 Jane Doe }\n"),
+				"{}",
+				pascal
+			);
+			assert!(pascal.contains("{\n License:\n MIT\n}"), "{}", pascal);
+			assert_eq!(annotated(name, &pascal, &options), pascal);
+		}
+	}
+
+	#[test]
+	fn footer_sections_are_inserted_and_updated_independently() {
+		let mut options = options("Jane Doe");
+		options.footer_sections = vec![
+			("Contributors".to_string(), "{author}, {year}".to_string()),
+			("Notes".to_string(), "Generated in part.".to_string()),
+		];
+		let first = annotated("main.rs", "fn a() {}\n", &options);
+		assert!(
+			first.ends_with(
+				"/*\n * License:\n * MIT\n */\n\n/*\n * Contributors:\n * Jane Doe, 2026\n */\n\n\
+			 /*\n * Notes:\n * Generated in part.\n */"
+			),
+			"{}",
+			first
+		);
+		assert_eq!(annotated("main.rs", &first, &options), first);
+
+		options.footer_sections[0].1 = "{author} and friends".to_string();
+		let second = annotated("main.rs", &first, &options);
+		assert_eq!(
+			second,
+			first.replace(" * Jane Doe, 2026\n", " * Jane Doe and friends\n")
+		);
+		for label in ["License:", "Contributors:", "Notes:"] {
+			assert_eq!(second.matches(label).count(), 1, "{}", label);
+		}
+	}
+
+	#[test]
+	fn failed_commit_leaves_committed_files_valid_and_the_rest_untouched() {
+		let dir = TempDir::new();
+		let mut options = options("Jane Doe");
+		options.staged = Some(RefCell::default());
+		for name in ["a.rs", "b.rs", "c.rs"] {
+			let file = dir.write(name, "fn old() {}\n");
+			assert!(update_file(&file, &options).unwrap() == Outcome::Changed);
+			assert_eq!(fs::read_to_string(&file).unwrap(), "fn old() {}\n");
+		}
+		let files = options.staged.take().unwrap().into_inner();
+		let updated = fs::read_to_string(staged_path(&files[0])).unwrap();
+		// The second rename fails because its staging file has gone missing.
+		fs::remove_file(staged_path(&files[1])).unwrap();
+
+		assert!(commit_staged(&files, &options).is_err());
+		assert_eq!(fs::read_to_string(&files[0]).unwrap(), updated);
+		assert!(updated.starts_with("/* Copyright (c) 2026 Jane Doe  */\n"));
+		assert_eq!(fs::read_to_string(&files[1]).unwrap(), "fn old() {}\n");
+		assert_eq!(fs::read_to_string(&files[2]).unwrap(), "fn old() {}\n");
+		for file in &files {
+			assert!(!staged_path(file).exists(), "{}", file.display());
+		}
+	}
+
+	#[test]
+	fn nested_ml_comments_are_delimited_by_depth() {
+		let mut options = options("Jane Doe");
+		options.license = "MIT (* see LICENSE *)".to_string();
+		let source = "(* Copyright (c) 2024 Jane Doe (* and contributors *) *)\n\nlet x = 1\n";
+		let first = annotated("main.ml", source, &options);
+		assert!(
+			first.starts_with("(* Copyright (c) 2024-2026 Jane Doe (* and contributors *) *)\n"),
+			"{}",
+			first
+		);
+		assert!(
+			first.ends_with("(*\n * License:\n * MIT (* see LICENSE *)\n *)"),
+			"{}",
+			first
+		);
+		let footers = find_footer_blocks(&first, "(*", " *)", &["License"]);
+		assert_eq!(footers.len(), 1);
+		assert!(first[footers[0].clone()].ends_with("LICENSE *)\n *)"));
+		assert_eq!(annotated("main.ml", &first, &options), first);
+	}
+
+	#[test]
+	fn indented_header_and_footer_are_stable() {
+		let mut options = options("Jane Doe");
+		for indent in ["    ", "\t"] {
+			options.indent = indent.to_string();
+			let first = annotated("gen.rs", "fn a() {}\n", &options);
+			assert!(
+				first.starts_with(&format!("{}/* Copyright (c) 2026 Jane Doe  */\n", indent)),
+				"{:?}",
+				first
+			);
+			let footer = format!("{0}/*\n{0} * License:\n{0} * MIT\n{0} */", indent);
+			assert!(first.ends_with(&footer), "{:?}", first);
+			assert_eq!(annotated("gen.rs", &first, &options), first);
+		}
+	}
+
+	#[test]
+	fn only_the_authors_holder_line_is_bumped() {
+		let options = options("Jane Doe");
+		let source = "/*\n * Copyright (c) 2019 Acme Corp\n * Copyright (c) 2020 Jane Doe\n \
+		              * Copyright (c) 2021-2022 Globex\n */\n\nfn a() {}\n";
+		let content = annotated("main.rs", source, &options);
+		assert!(
+			content.starts_with(
+				"/*\n * Copyright (c) 2019 Acme Corp\n * Copyright (c) 2020-2026 Jane Doe\n \
+				 * Copyright (c) 2021-2022 Globex\n */\n\nfn a() {}\n"
+			),
+			"{}",
+			content
+		);
+		assert_eq!(annotated("main.rs", &content, &options), content);
+	}
+
+	#[test]
+	fn notices_outside_comments_are_not_holder_lines() {
+		let options = options("Jane Doe");
+		let source = "x = \"Copyright (c) 2020 Jane Doe\"\n";
+		let content = annotated("repro.py", source, &options);
+		assert!(
+			content.starts_with("# Copyright (c) 2026 Jane Doe #\n"),
+			"{}",
+			content
+		);
+		assert!(content.contains("\nx = \"Copyright (c) 2020 Jane Doe\"\n"));
+
+		// Inside a `<script>` block, a script comment still counts.
+		let source = "<script>\n// Copyright (c) 2020 Jane Doe\n</script>\n";
+		let content = annotated("App.svelte", source, &options);
+		assert!(
+			content.starts_with("<script>\n// Copyright (c) 2020-2026 Jane Doe\n"),
+			"{}",
+			content
+		);
+	}
+
+	#[test]
+	fn header_trailer_appears_once() {
+		let mut options = options("Jane Doe");
+		options.header_trailer = Some("----------".to_string());
+		let first = annotated("a.py", "x = 1\n", &options);
+		assert!(
+			first.starts_with("# Copyright (c) 2026 Jane Doe #\n# ---------- #\n\nx = 1\n"),
+			"{}",
+			first
+		);
+		let second = annotated("a.py", &first, &options);
+		assert_eq!(second, first);
+		options.now = Utc.with_ymd_and_hms(2027, 1, 1, 0, 0, 0).unwrap();
+		let third = annotated("a.py", &second, &options);
+		assert!(third.starts_with("# Copyright (c) 2026-2027 Jane Doe #\n# ---------- #\n\nx"));
+		assert_eq!(third.matches("----------").count(), 1);
+	}
+
+	#[test]
+	fn eof_comment_is_added_once() {
+		let mut options = options("Jane Doe");
+		options.eof_comment = Some("// EOF".to_string());
+		let first = annotated("main.rs", "fn a() {}\n", &options);
+		assert!(first.ends_with(" * MIT\n */\n// EOF"), "{}", first);
+		assert_eq!(
+			annotated("main.rs", &format!("{}\n", first), &options),
+			first
+		);
+		assert_eq!(first.matches("// EOF").count(), 1);
+	}
+
+	#[test]
+	fn svelte_and_astro_headers_go_at_the_very_top() {
+		let options = options("Jane Doe");
+		let svelte = "<script>\n  let name = 'world';\n</script>\n\n<h1>Hello {name}!</h1>\n";
+		let content = annotated("App.svelte", svelte, &options);
+		assert!(
+			content.starts_with("<!-- Copyright (c) 2026 Jane Doe -->\n\n<script>\n"),
+			"{}",
+			content
+		);
+		assert!(
+			content.contains("<!--\n License:\n MIT\n-->"),
+			"{}",
+			content
+		);
+
+		let astro = "---\nconst title = 'Home';\n---\n<h1>{title}</h1>\n";
+		let content = annotated("index.astro", astro, &options);
+		assert!(
+			content.starts_with("<!-- Copyright (c) 2026 Jane Doe -->\n\n---\nconst title"),
+			"{}",
+			content
+		);
+		assert_eq!(annotated("index.astro", &content, &options), content);
+	}
+
+	#[test]
+	fn symbol_only_header_is_converted_to_the_spelled_form() {
+		let mut options = options("Jane Doe");
+		options.copyright_word = Some("Copyright");
+		let source = "/* (c) 2024 Jane Doe  */\n\nfn a() {}\n";
+		let content = annotated("main.rs", source, &options);
+		assert!(
+			content.starts_with("/* Copyright 2024-2026 Jane Doe  */\n"),
+			"{}",
+			content
+		);
+		assert_eq!(annotated("main.rs", &content, &options), content);
+		options.copyright_word = Some("(c)");
+		let content = annotated("main.rs", &content, &options);
+		assert!(
+			content.starts_with("/* (c) 2024-2026 Jane Doe  */\n"),
+			"{}",
+			content
+		);
+	}
+
+	#[test]
+	fn shebang_stays_on_the_first_line() {
+		let options = options("Jane Doe");
+		let script = "#!/bin/sh\necho hi\n";
+		let first = annotated("run.sh", script, &options);
+		assert!(
+			first.starts_with("#!/bin/sh\n\n# Copyright (c) 2026 Jane Doe #\n\necho hi\n"),
+			"{}",
+			first
+		);
+		assert_eq!(annotated("run.sh", &first, &options), first);
+
+		let plain = annotated("run.sh", "echo hi\n", &options);
+		assert!(
+			plain.starts_with("# Copyright (c) 2026 Jane Doe #\n\necho hi\n"),
+			"{}",
+			plain
+		);
+
+		// Only a shebang on line one counts.
+		let late = annotated("run.sh", "echo hi\n#!/bin/sh\n", &options);
+		assert!(late.starts_with("# Copyright (c) 2026 Jane Doe #\n\necho hi\n#!/bin/sh\n"));
+
+		// An existing header below the shebang is updated in place.
+		let mut next_year = options;
+		next_year.now = Utc.with_ymd_and_hms(2027, 3, 1, 0, 0, 0).unwrap();
+		let updated = annotated("run.sh", &first, &next_year);
+		assert_eq!(
+			updated,
+			first.replace("(c) 2026 Jane", "(c) 2026-2027 Jane")
+		);
+	}
+
+	#[test]
+	fn vue_header_inside_script_is_not_duplicated() {
+		let options = options("Jane Doe");
+		let source = "<template>\n  <p>Hi</p>\n</template>\n\n<script>\n/* Copyright (c) 2024 Jane Doe */\nexport default {};\n</script>\n";
+		let content = annotated("App.vue", source, &options);
+		assert_eq!(content.matches("Copyright").count(), 1, "{}", content);
+		assert!(
+			content.contains("<script>\n/* Copyright (c) 2024-2026 Jane Doe */\n"),
+			"{}",
+			content
+		);
+		assert!(content.starts_with("<template>\n"));
+		assert_eq!(annotated("App.vue", &content, &options), content);
+	}
+
+	#[test]
+	fn oldest_files_come_first_and_footers_do_not_count() {
+		let dir = TempDir::new();
+		let newest = dir.write("c.py", "# Copyright (c) 2015-2025 Acme #\nx = 1\n");
+		let oldest = dir.write("a.py", "# Copyright (c) 2012 Acme #\nx = 1\n");
+		let middle = dir.write(
+			"b.rs",
+			"/* Copyright (c) 2018, 2020 Jane Doe  */\n\nfn b() {}\n\n\
+			 /*\n * License:\n * Copyright (c) 2099 Licensor\n */\n",
+		);
+		let footer_only = dir.write(
+			"d.rs",
+			"fn d() {}\n\n/*\n * License:\n * Copyright (c) 2001 Licensor\n */\n",
+		);
+		let files = [newest.clone(), oldest.clone(), middle.clone(), footer_only];
+		assert_eq!(
+			oldest_files(&files),
+			[(2012, oldest), (2020, middle), (2025, newest)]
+		);
+	}
+
+	#[test]
+	fn workflow_files_get_a_hash_header_above_name() {
+		let workflow = "name: CI\non:\n  push:\njobs:\n  build:\n    runs-on: ubuntu-latest\n    steps:\n      - run: make\n";
+		let path = ".github/workflows/ci.yml";
+		assert!(is_workflow_file(Path::new(path)));
+		assert!(!is_workflow_file(Path::new("config/ci.yml")));
+		let content = annotated(path, workflow, &options("Jane Doe"));
+		assert!(
+			content.starts_with("# Copyright (c) 2026 Jane Doe #\n\nname: CI\n"),
+			"{}",
+			content
+		);
+		// Only comment and blank lines were added, so the document is unchanged as YAML.
+		let data: String = content
+			.lines()
+			.filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+			.map(|line| format!("{}\n", line))
+			.collect();
+		assert_eq!(data, workflow);
+	}
+
+	#[test]
+	fn messy_licenses_give_clean_stable_footers() {
+		let messy = "\r\n\r\nMIT  License \r\n\r\n\r\nPermission is\t hereby granted,   free \r\n  of charge.\t\r\n\r\n";
+		let clean = normalize_license(messy, None);
+		assert_eq!(
+			clean,
+			"MIT License\n\nPermission is hereby granted, free\n  of charge."
+		);
+		assert_eq!(normalize_license(&clean, None), clean);
+		assert_eq!(
+			normalize_license(messy, Some(20)),
+			"MIT License\n\nPermission is hereby\ngranted, free of\ncharge."
+		);
+
+		let mut options = options("Jane Doe");
+		options.license = clean;
+		let content = annotated("main.rs", "fn a() {}\n", &options);
+		assert!(content.ends_with(
+			"/*\n * License:\n * MIT License\n *\n * Permission is hereby granted, free\n *   of charge.\n */"
+		), "{:?}", content);
+		assert!(!content.contains('\r') && !content.contains(" \n"));
+	}
+
+	#[test]
+	fn holder_lines_are_ordered_on_request() {
+		let source = "/*\n * Copyright (c) 2021 Globex\n * Copyright (c) 2019 Jane Doe\n \
+		              * Copyright (c) 2023 Acme Corp\n */\n\nfn a() {}\n";
+		let mut options = options("Jane Doe");
+		options.holder_order = "alpha".to_string();
+		let content = annotated("main.rs", source, &options);
+		assert!(
+			content.starts_with(
+				"/*\n * Copyright (c) 2023 Acme Corp\n * Copyright (c) 2021 Globex\n \
+				 * Copyright (c) 2019-2026 Jane Doe\n */\n"
+			),
+			"{}",
+			content
+		);
+		assert_eq!(annotated("main.rs", &content, &options), content);
+
+		options.holder_order = "year".to_string();
+		let content = annotated("main.rs", source, &options);
+		assert!(
+			content.starts_with(
+				"/*\n * Copyright (c) 2019-2026 Jane Doe\n * Copyright (c) 2021 Globex\n \
+			 * Copyright (c) 2023 Acme Corp\n */\n"
+			),
+			"{}",
+			content
+		);
+	}
+
+	#[test]
+	fn copyright_is_folded_into_an_existing_license_block() {
+		let mut options = options("Jane Doe");
+		options.merge_license_block = true;
+		let imported = "/*\n * Copyright (c) 2015 Upstream Authors\n *\n * Licensed under the Apache License, Version 2.0.\n */\n\nfn a() {}\n";
+		let content = annotated("lib.rs", imported, &options);
+		assert_eq!(
+			content,
+			"/*\n * Copyright (c) 2026 Jane Doe\n * Copyright (c) 2015 Upstream Authors\n *\n \
+			 * Licensed under the Apache License, Version 2.0.\n */\n\nfn a() {}\n"
+		);
+		assert!(!content.contains("License:"));
+		assert_eq!(annotated("lib.rs", &content, &options), content);
+
+		let lines = "# Permission is hereby granted, free of charge.\n\nx = 1\n";
+		let content = annotated("a.py", lines, &options);
+		assert!(
+			content.starts_with("# Copyright (c) 2026 Jane Doe\n# Permission"),
+			"{}",
+			content
+		);
+		assert!(!content.contains("License:"));
+	}
+
+	#[test]
+	fn year_lists_beyond_the_maximum_collapse_to_a_range() {
+		assert_eq!(
+			extend_years("2010, 2014, 2018", 2022, true, Some(4)).as_deref(),
+			Some("2010, 2014, 2018, 2022")
+		);
+		assert_eq!(
+			extend_years("2010, 2014, 2018, 2022", 2026, true, Some(4)).as_deref(),
+			Some("2010-2026")
+		);
+		// A list already over the maximum collapses even when the year is current.
+		assert_eq!(
+			extend_years("2010, 2014, 2018, 2026", 2026, true, Some(3)).as_deref(),
+			Some("2010-2026")
+		);
+		assert_eq!(extend_years("2010-2026", 2026, true, Some(3)), None);
+
+		let mut options = options("Jane Doe");
+		options.preserve_gaps = true;
+		options.year_list_max = Some(2);
+		let source = "# Copyright (c) 2012, 2020 Jane Doe #\n\nx = 1\n";
+		let content = annotated("a.py", source, &options);
+		assert!(
+			content.starts_with("# Copyright (c) 2012-2026 Jane Doe #\n"),
+			"{}",
+			content
+		);
+	}
+
+	#[test]
+	fn cuda_and_objective_cpp_get_c_style_headers() {
+		let dir = TempDir::new();
+		let options = options("Jane Doe");
+		for name in ["kernel.cu", "kernel.cuh", "View.mm"] {
+			assert!(is_source_file(Path::new(name)), "{}", name);
+			let content = annotated(name, "int x;\n", &options);
+			assert!(
+				content.starts_with("/* Copyright (c) 2026 Jane Doe  */\n"),
+				"{}",
+				content
+			);
+			assert!(
+				content.contains("/*\n * License:\n * MIT\n */"),
+				"{}",
+				content
+			);
+		}
+		// A FreeMind map shares the `.mm` extension but is XML, not source.
+		let mind_map = dir.write("ideas.mm", "<map version=\"1.0.1\">\n</map>\n");
+		assert!(read_source(&mind_map, 0).unwrap() == Err(SkipReason::NotSource));
+	}
+
+	#[test]
+	fn files_under_the_size_floor_are_skipped() {
+		let dir = TempDir::new();
+		let empty = dir.write("mod.rs", "");
+		let stub = dir.write("__init__.py", "\n");
+		let mut options = options("Jane Doe");
+		options.min_file_size = 1;
+		assert!(update_file(&empty, &options).unwrap() == Outcome::Skipped(SkipReason::Small));
+		assert_eq!(fs::read_to_string(&empty).unwrap(), "");
+		assert_eq!(SkipReason::Small.as_str(), "too-small");
+		assert!(update_file(&stub, &options).unwrap() == Outcome::Changed);
+		// The default floor of 0 processes even empty files.
+		options.min_file_size = 0;
+		assert!(update_file(&empty, &options).unwrap() == Outcome::Changed);
+	}
+
+	#[test]
+	fn duplicate_headers_fail_the_check() {
+		let dir = TempDir::new();
+		let mut options = options("Jane Doe");
+		let clean = dir.write("clean.rs", &annotated("clean.rs", "fn a() {}\n", &options));
+		let duplicated = dir.write(
+			"dup.rs",
+			&annotated("dup.rs", "fn a() {}\n", &options).replace(
+				"/* Copyright (c) 2026 Jane Doe  */\n",
+				"/* Copyright (c) 2026 Jane Doe  */\n/* Copyright (c) 2019 Jane Doe  */\n",
+			),
+		);
+		let holders = dir.write(
+			"holders.rs",
+			&annotated(
+				"holders.rs",
+				"/* Copyright (c) 2019 Acme  */\n\nfn a() {}\n",
+				&options,
+			),
+		);
+		// A notice line repeated inside a multi-line block counts as well.
+		let block = dir.write(
+			"block.rs",
+			"/*\n * Copyright (c) 2019 Jane Doe\n * Copyright (c) 2019 Jane Doe\n */\n\nfn a() {}\n",
+		);
+		options.check = true;
+		options.no_duplicate_headers = true;
+		assert!(update_file(&duplicated, &options).unwrap() == Outcome::Mismatch);
+		assert!(update_file(&block, &options).unwrap() == Outcome::Mismatch);
+		assert!(update_file(&clean, &options).unwrap() == Outcome::Unchanged);
+		assert!(update_file(&holders, &options).unwrap() == Outcome::Unchanged);
+		options.no_duplicate_headers = false;
+		assert!(update_file(&duplicated, &options).unwrap() != Outcome::Mismatch);
+	}
+
+	#[test]
+	fn bazel_files_get_hash_headers() {
+		let options = options("Jane Doe");
+		for name in [
+			"BUILD",
+			"BUILD.bazel",
+			"WORKSPACE",
+			"WORKSPACE.bazel",
+			"defs.bzl",
+		] {
+			let path = Path::new("pkg").join(name);
+			assert!(
+				is_source_file(&path) || is_bazel_build_file(&path),
+				"{}",
+				name
+			);
+			let content = annotated(
+				path.to_str().unwrap(),
+				"load(\"//x:y.bzl\", \"z\")\n",
+				&options,
+			);
+			assert!(
+				content.starts_with("# Copyright (c) 2026 Jane Doe #\n\nload("),
+				"{}: {}",
+				name,
+				content
+			);
+		}
+		assert!(!is_bazel_build_file(Path::new("BUILDING.md")));
+	}
+
+	#[test]
+	fn license_lookups_search_each_directory_once() {
+		let dir = TempDir::new();
+		let license = dir.write("LICENSE", "MIT");
+		let files: Vec<PathBuf> = (0..10)
+			.map(|i| dir.write(&format!("src/deep/f{}.rs", i), "fn a() {}\n"))
+			.collect();
+		let lookup = LicenseLookup::new(&["LICENSE"]);
+		for file in &files {
+			assert_eq!(lookup.find(file), Some(license.clone()));
+		}
+		// src/deep, src, and the root, each searched by the first lookup only.
+		assert_eq!(lookup.probes.load(Ordering::Relaxed), 3);
+		let other = dir.write("src/other/g.rs", "fn g() {}\n");
+		assert_eq!(lookup.find(&other), Some(license));
+		assert_eq!(lookup.probes.load(Ordering::Relaxed), 4);
+	}
+
+	#[test]
+	fn parallel_license_lookups_search_each_directory_once() {
+		let dir = TempDir::new();
+		let license = dir.write("LICENSE", "MIT");
+		let nested = dir.write("pkg/LICENSE", "Apache");
+		let mut dirs = Vec::new();
+		for i in 0..8 {
+			for j in 0..4 {
+				let file = dir.write(&format!("pkg/m{}/f{}.rs", i, j), "fn a() {}\n");
+				dirs.push(file.parent().unwrap().to_path_buf());
+			}
+		}
+		let top = dir.write("src/main.rs", "fn main() {}\n");
+		dirs.push(top.parent().unwrap().to_path_buf());
+		let lookup = LicenseLookup::new(&["LICENSE"]);
+		lookup.resolve_parallel(&dirs);
+		// pkg/m0 to pkg/m7, pkg, src, and the root, however the threads interleave.
+		assert_eq!(lookup.probes.load(Ordering::Relaxed), 11);
+		assert_eq!(lookup.find(&dirs[0]), Some(nested));
+		assert_eq!(lookup.find(&top), Some(license));
+		assert_eq!(lookup.probes.load(Ordering::Relaxed), 11);
+	}
+
+	#[test]
+	fn punctuation_variants_normalize_to_one_form() {
+		let mut options = options("Jane Doe");
+		options.normalize_punctuation = true;
+		let canonical = "/* Copyright (c) 2020-2026 Jane Doe  */\n\nfn a() {}\n";
+		for header in [
+			"/* Copyright (c) 2020 Jane Doe  */",
+			"/* Copyright(c) 2020 Jane Doe  */",
+			"/* Copyright © 2020, Jane Doe  */",
+			"/* Copyright  (C)  2020,Jane Doe  */",
+			"/* © 2020 Jane Doe  */",
+		] {
+			let content = annotated("main.rs", &format!("{}\n\nfn a() {{}}\n", header), &options);
+			assert!(content.starts_with(canonical), "{}: {}", header, content);
+			assert_eq!(content.matches("Copyright").count(), 1, "{}", content);
+			assert_eq!(annotated("main.rs", &content, &options), content);
+		}
+	}
 }
 
 /*
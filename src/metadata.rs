@@ -0,0 +1,234 @@
+/* Copyright (c) 2025 Eric Hernandez  */
+
+//! Copyright metadata written as data rather than comments, for `--structured-metadata`.
+//!
+//! YAML files get a top-level `copyright:` mapping and TOML files a `[copyright]`
+//! table, each with `author` and `year` fields. The files are edited line by line so
+//! the rest of the document keeps its layout, comments, and key order.
+
+use std::path::Path;
+
+/// A data format that can carry copyright metadata.
+#[derive(Clone, Copy)]
+pub enum Format {
+	Yaml,
+	Toml,
+}
+
+impl Format {
+	/// The metadata format of `path`, judged by its extension.
+	pub fn of(path: &Path) -> Option<Format> {
+		let ext = path.extension()?.to_str()?.to_lowercase();
+		match ext.as_str() {
+			"yaml" | "yml" => Some(Format::Yaml),
+			"toml" => Some(Format::Toml),
+			_ => None,
+		}
+	}
+
+	/// Whether `line` opens the copyright table.
+	fn is_table(self, line: &str) -> bool {
+		match self {
+			Format::Yaml => line.trim_end() == "copyright:",
+			Format::Toml => line.trim() == "[copyright]",
+		}
+	}
+
+	/// Whether `line`, following the table header, still belongs to the table.
+	fn in_table(self, line: &str) -> bool {
+		match self {
+			Format::Yaml => line.starts_with([' ', '\t']) || line.trim().is_empty(),
+			Format::Toml => !line.trim_start().starts_with('['),
+		}
+	}
+
+	/// The value of `key` if `line` assigns it.
+	fn field_value<'a>(self, line: &'a str, key: &str) -> Option<&'a str> {
+		let separator = match self {
+			Format::Yaml => ':',
+			Format::Toml => '=',
+		};
+		let rest = line.trim_start().strip_prefix(key)?.trim_start();
+		Some(rest.strip_prefix(separator)?.trim())
+	}
+
+	/// A line assigning `value` to `key` inside the table.
+	fn field(self, key: &str, value: &str) -> String {
+		match self {
+			Format::Yaml => format!("  {}: {}", key, quote(value)),
+			Format::Toml => format!("{} = {}", key, quote(value)),
+		}
+	}
+
+	/// The complete table, for files that do not have one yet.
+	fn table(self, author: &str, years: &str) -> String {
+		let header = match self {
+			Format::Yaml => "copyright:",
+			Format::Toml => "[copyright]",
+		};
+		format!(
+			"{}\n{}\n{}\n",
+			header,
+			self.field("author", author),
+			self.field("year", years)
+		)
+	}
+}
+
+/// Double-quotes `value`; the escaping is valid in both YAML and TOML.
+fn quote(value: &str) -> String {
+	format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Strips the quotes `quote` or a hand-written file may have put around a value.
+fn unquote(value: &str) -> String {
+	let inner = ['"', '\'']
+		.iter()
+		.find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+		.unwrap_or(value);
+	inner.replace("\\\"", "\"").replace("\\\\", "\\")
+}
+
+/// Returns `content` with its copyright metadata set to `author` and extended to
+/// `current_year`, or `None` if the file cannot take a top-level key (a YAML document
+/// whose root is a sequence or scalar).
+pub fn annotate(
+	format: Format,
+	content: &str,
+	author: &str,
+	current_year: i32,
+	preserve_gaps: bool,
+	year_list_max: Option<usize>,
+) -> Option<String> {
+	let mut lines: Vec<String> = content.split_inclusive('\n').map(str::to_string).collect();
+
+	let Some(table) = lines.iter().position(|line| format.is_table(line)) else {
+		let years = current_year.to_string();
+		return match format {
+			// A new table may go anywhere after the top-level keys, so the end is safe.
+			Format::Toml if content.trim().is_empty() => Some(format.table(author, &years)),
+			Format::Toml => Some(format!(
+				"{}\n\n{}",
+				content.trim_end(),
+				format.table(author, &years)
+			)),
+			// The key goes first, after any comments and the document start marker.
+			Format::Yaml => {
+				let body = lines
+					.iter()
+					.position(|line| {
+						let line = line.trim();
+						!(line.is_empty()
+							|| line.starts_with('#')
+							|| line.starts_with('%')
+							|| line == "---")
+					})
+					.unwrap_or(lines.len());
+				if lines.get(body).is_some_and(|line| {
+					line.starts_with(['-', '[', '{', '"', '\'']) || !line.contains(':')
+				}) {
+					return None;
+				}
+				lines.insert(body, format.table(author, &years));
+				Some(lines.concat())
+			}
+		};
+	};
+
+	let end = (table + 1..lines.len())
+		.find(|&i| !format.in_table(&lines[i]))
+		.unwrap_or(lines.len());
+	let mut seen_author = false;
+	let mut seen_year = false;
+	for line in &mut lines[table + 1..end] {
+		let newline = &line[line.trim_end_matches(['\r', '\n']).len()..];
+		if let Some(value) = format.field_value(line, "author") {
+			seen_author = true;
+			if unquote(value) != author {
+				*line = format.field("author", author) + newline;
+			}
+		} else if let Some(value) = format.field_value(line, "year") {
+			seen_year = true;
+			let years = unquote(value);
+			let updated = if crate::is_years(&years) {
+				crate::extend_years(&years, current_year, preserve_gaps, year_list_max)
+			} else {
+				Some(current_year.to_string())
+			};
+			if let Some(updated) = updated {
+				*line = format.field("year", &updated) + newline;
+			}
+		}
+	}
+	if !seen_year {
+		lines.insert(
+			table + 1,
+			format.field("year", &current_year.to_string()) + "\n",
+		);
+	}
+	if !seen_author {
+		lines.insert(table + 1, format.field("author", author) + "\n");
+	}
+	if !lines[table].ends_with('\n') {
+		lines[table].push('\n');
+	}
+	Some(lines.concat())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn toml_gets_a_copyright_table_whose_year_is_updated() {
+		let content = "# Settings\n[server]\nport = 8080\n";
+		let first = annotate(Format::Toml, content, "Jane Doe", 2025, false, None).unwrap();
+		assert_eq!(
+			first,
+			"# Settings\n[server]\nport = 8080\n\n[copyright]\nauthor = \"Jane Doe\"\nyear = \"2025\"\n"
+		);
+		assert_eq!(
+			annotate(Format::Toml, &first, "Jane Doe", 2025, false, None).unwrap(),
+			first
+		);
+		let second = annotate(Format::Toml, &first, "Jane Doe", 2026, false, None).unwrap();
+		assert_eq!(
+			second,
+			first.replace("year = \"2025\"", "year = \"2025-2026\"")
+		);
+	}
+
+	#[test]
+	fn yaml_gets_a_leading_copyright_mapping() {
+		let content = "# CI\n---\nname: build\non: push\n";
+		let annotated = annotate(Format::Yaml, content, "Jane Doe", 2026, false, None).unwrap();
+		assert_eq!(
+			annotated,
+			"# CI\n---\ncopyright:\n  author: \"Jane Doe\"\n  year: \"2026\"\nname: build\non: push\n"
+		);
+		assert!(annotate(Format::Yaml, "- a\n- b\n", "Jane Doe", 2026, false, None).is_none());
+	}
+}
+
+/*
+ * License:
+ * Copyright (c) 2025 Eric Hernandez
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
@@ -0,0 +1,147 @@
+/* Copyright (c) 2025 Eric Hernandez  */
+
+//! The numbered change plan that `--plan` saves during a dry run, so a follow-up run
+//! can apply a chosen part of it with `--apply-selected`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+/// Write `files`, the files a dry run would change, to `path` in plan order. Entry
+/// `n` of the plan is `files[n - 1]`.
+pub fn save(path: &Path, files: &[&Path]) -> io::Result<()> {
+	let files: Vec<_> = files.iter().map(|file| file.to_string_lossy()).collect();
+	let value = json!({ "files": files });
+	fs::write(path, serde_json::to_string_pretty(&value).unwrap() + "\n")
+}
+
+/// Load the files of the plan at `path`, in plan order.
+pub fn load(path: &Path) -> io::Result<Vec<PathBuf>> {
+	let invalid = |message: &str| {
+		io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("Invalid plan file {}: {}", path.display(), message),
+		)
+	};
+	let value: Value =
+		serde_json::from_str(&fs::read_to_string(path)?).map_err(|e| invalid(&e.to_string()))?;
+	value["files"]
+		.as_array()
+		.ok_or_else(|| invalid("expected a \"files\" array"))?
+		.iter()
+		.map(|file| {
+			file.as_str()
+				.map(PathBuf::from)
+				.ok_or_else(|| invalid("expected file paths"))
+		})
+		.collect()
+}
+
+/// Picks the entries of `plan` named by `ranges`, a comma-separated list of entry
+/// numbers and inclusive ranges such as `1,3-5`, in plan order and without repeats.
+pub fn select(plan: &[PathBuf], ranges: &str) -> Result<Vec<PathBuf>, String> {
+	let mut selected = vec![false; plan.len()];
+	for range in ranges
+		.split(',')
+		.map(str::trim)
+		.filter(|range| !range.is_empty())
+	{
+		let (first, last) = range.split_once('-').unwrap_or((range, range));
+		let parse = |number: &str| {
+			number
+				.trim()
+				.parse::<usize>()
+				.ok()
+				.filter(|n| (1..=plan.len()).contains(n))
+				.ok_or_else(|| {
+					format!(
+						"invalid selection {}: the plan has entries 1 to {}",
+						range,
+						plan.len()
+					)
+				})
+		};
+		let (first, last) = (parse(first)?, parse(last)?);
+		if first > last {
+			return Err(format!(
+				"invalid selection {}: the range ends before it starts",
+				range
+			));
+		}
+		for n in first..=last {
+			selected[n - 1] = true;
+		}
+	}
+	Ok(plan
+		.iter()
+		.zip(selected)
+		.filter(|(_, selected)| *selected)
+		.map(|(file, _)| file.clone())
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::TempDir;
+
+	#[test]
+	fn ranges_select_entries_in_plan_order() {
+		let plan: Vec<PathBuf> = ["a", "b", "c", "d", "e"]
+			.iter()
+			.map(PathBuf::from)
+			.collect();
+		let selected = select(&plan, "4-5, 1,4").unwrap();
+		assert_eq!(selected, [PathBuf::from("a"), "d".into(), "e".into()]);
+		assert_eq!(
+			select(&plan, "2-6").unwrap_err(),
+			"invalid selection 2-6: the plan has entries 1 to 5"
+		);
+		assert_eq!(
+			select(&plan, "5-3").unwrap_err(),
+			"invalid selection 5-3: the range ends before it starts"
+		);
+		assert!(select(&plan, "x").is_err());
+	}
+
+	#[test]
+	fn saved_plans_load_back() {
+		let dir = TempDir::new();
+		let path = dir.path().join("plan.json");
+		save(&path, &[Path::new("./src/a.rs"), Path::new("./b.py")]).unwrap();
+		assert_eq!(
+			load(&path).unwrap(),
+			[PathBuf::from("./src/a.rs"), "./b.py".into()]
+		);
+		let bad = dir.write("bad.json", "{ \"files\": [1] }");
+		assert!(load(&bad)
+			.unwrap_err()
+			.to_string()
+			.ends_with("expected file paths"));
+	}
+}
+
+/*
+ * License:
+ * Copyright (c) 2025 Eric Hernandez
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
@@ -0,0 +1,349 @@
+/* Copyright (c) 2025 Eric Hernandez  */
+
+//! Per-file-type rules, given with `--rules`, that settle in one place how each kind
+//! of file is annotated.
+//!
+//! The rules file is a JSON object with a `rules` array. Each rule names the files it
+//! covers by `extensions` and/or `files` (exact file names) and may set `comment` (the
+//! opening delimiter of one of the known comment styles), `footer` (`false` to leave
+//! the license footer out), and `format` (`comments`, or `metadata` to record the
+//! copyright as data in YAML and TOML files):
+//!
+//! ```json
+//! { "rules": [
+//!     { "extensions": ["rs"], "comment": "//", "footer": false },
+//!     { "files": ["Makefile"], "comment": "#" }
+//! ] }
+//! ```
+//!
+//! Language definitions, loaded from a `--lang-defs` directory of TOML files, become
+//! rules too, ranked below those of the rules file. Each file defines one language:
+//!
+//! ```toml
+//! extensions = ["foo"]
+//! files = ["Foofile"]
+//! comment = { start = "##", prefix = "## ", end = "##" }
+//! interpreters = ["foo"]
+//! ```
+//!
+//! `comment` may also name a known style by its opening delimiter, as in rules files,
+//! and `interpreters` lists the programs whose `#!` lines mark extensionless files as
+//! being in the language.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json::Value;
+
+/// How the files matched by one rule are annotated.
+#[derive(Clone)]
+pub struct Rule {
+	/// Lowercase extensions, without the dot, that the rule covers.
+	extensions: Vec<String>,
+	/// Exact file names that the rule covers.
+	file_names: Vec<String>,
+	/// The comment style to write headers and footers in, overriding the file type's.
+	pub comment_style: Option<(&'static str, &'static str, &'static str)>,
+	/// Whether the file gets a license footer.
+	pub footer: bool,
+	/// Whether the copyright is recorded as structured metadata; unset defers to
+	/// `--structured-metadata`.
+	pub structured: Option<bool>,
+	/// Interpreters whose shebang lines mark a file as covered by the rule.
+	interpreters: Vec<String>,
+	/// Where the rule was defined, for `--verbose`.
+	pub origin: &'static str,
+}
+
+/// The rules of a rules file, in the order they were given.
+#[derive(Clone, Default)]
+pub struct Rules(Vec<Rule>);
+
+impl Rules {
+	/// Load the rules file at `path`. Comment styles are looked up by their opening
+	/// delimiter in `styles`.
+	pub fn load(
+		path: &Path,
+		styles: &[(&'static str, &'static str, &'static str)],
+	) -> io::Result<Rules> {
+		let invalid = |message: String| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("Invalid rules file {}: {}", path.display(), message),
+			)
+		};
+		let text = fs::read_to_string(path)?;
+		let value: Value = serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+		let Some(entries) = value["rules"].as_array() else {
+			return Err(invalid("expected a \"rules\" array".into()));
+		};
+
+		let mut rules = Vec::new();
+		for (i, entry) in entries.iter().enumerate() {
+			let strings = |key: &str| -> io::Result<Vec<String>> {
+				match &entry[key] {
+					Value::Null => Ok(Vec::new()),
+					Value::Array(items) => items
+						.iter()
+						.map(|item| {
+							item.as_str().map(str::to_string).ok_or_else(|| {
+								invalid(format!("rule {}: \"{}\" must list strings", i + 1, key))
+							})
+						})
+						.collect(),
+					_ => Err(invalid(format!(
+						"rule {}: \"{}\" must be an array",
+						i + 1,
+						key
+					))),
+				}
+			};
+			let extensions: Vec<String> = strings("extensions")?
+				.into_iter()
+				.map(|ext| ext.trim_start_matches('.').to_lowercase())
+				.collect();
+			let file_names = strings("files")?;
+			if extensions.is_empty() && file_names.is_empty() {
+				return Err(invalid(format!("rule {} matches no files", i + 1)));
+			}
+
+			let comment_style = match &entry["comment"] {
+				Value::Null => None,
+				Value::String(opener) => Some(
+					styles
+						.iter()
+						.find(|(start, _, _)| start == opener)
+						.copied()
+						.ok_or_else(|| {
+							invalid(format!("rule {}: unknown comment style {}", i + 1, opener))
+						})?,
+				),
+				_ => {
+					return Err(invalid(format!(
+						"rule {}: \"comment\" must be a string",
+						i + 1
+					)))
+				}
+			};
+			let footer = match &entry["footer"] {
+				Value::Null => true,
+				Value::Bool(footer) => *footer,
+				_ => {
+					return Err(invalid(format!(
+						"rule {}: \"footer\" must be true or false",
+						i + 1
+					)))
+				}
+			};
+			let structured = match entry["format"].as_str() {
+				None if entry["format"].is_null() => None,
+				Some("comments") => Some(false),
+				Some("metadata") => Some(true),
+				_ => {
+					return Err(invalid(format!(
+						"rule {}: \"format\" must be \"comments\" or \"metadata\"",
+						i + 1
+					)))
+				}
+			};
+
+			rules.push(Rule {
+				extensions,
+				file_names,
+				comment_style,
+				footer,
+				structured,
+				interpreters: Vec::new(),
+				origin: "rules file",
+			});
+		}
+		Ok(Rules(rules))
+	}
+
+	/// Load every `.toml` language definition in `dir`, in file-name order. Comment
+	/// styles that are not among `styles` are kept for the rest of the run.
+	pub fn load_lang_defs(
+		dir: &Path,
+		styles: &[(&'static str, &'static str, &'static str)],
+	) -> io::Result<Rules> {
+		let mut paths: Vec<_> = fs::read_dir(dir)?
+			.map(|entry| entry.map(|entry| entry.path()))
+			.collect::<io::Result<_>>()?;
+		paths.retain(|path| path.extension().is_some_and(|ext| ext == "toml"));
+		paths.sort();
+
+		let mut rules = Vec::new();
+		for path in paths {
+			let invalid = |message: String| {
+				io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!(
+						"Invalid language definition {}: {}",
+						path.display(),
+						message
+					),
+				)
+			};
+			let def: toml::Table =
+				toml::from_str(&fs::read_to_string(&path)?).map_err(|e| invalid(e.to_string()))?;
+			let strings = |key: &str| -> io::Result<Vec<String>> {
+				match def.get(key) {
+					None => Ok(Vec::new()),
+					Some(toml::Value::Array(items)) => items
+						.iter()
+						.map(|item| {
+							item.as_str()
+								.map(str::to_string)
+								.ok_or_else(|| invalid(format!("\"{}\" must list strings", key)))
+						})
+						.collect(),
+					Some(_) => Err(invalid(format!("\"{}\" must be an array", key))),
+				}
+			};
+			let extensions: Vec<String> = strings("extensions")?
+				.into_iter()
+				.map(|ext| ext.trim_start_matches('.').to_lowercase())
+				.collect();
+			let file_names = strings("files")?;
+			let interpreters = strings("interpreters")?;
+			if extensions.is_empty() && file_names.is_empty() && interpreters.is_empty() {
+				return Err(invalid("it matches no files".into()));
+			}
+
+			let comment_style = match def.get("comment") {
+				Some(toml::Value::String(opener)) => styles
+					.iter()
+					.find(|(start, _, _)| start == opener)
+					.copied()
+					.ok_or_else(|| invalid(format!("unknown comment style {}", opener)))?,
+				Some(toml::Value::Table(style)) => {
+					let part = |key: &str| {
+						style
+							.get(key)
+							.and_then(toml::Value::as_str)
+							.filter(|part| !part.trim().is_empty() || key == "prefix")
+							.map(|part| &*Box::leak(part.to_string().into_boxed_str()))
+							.ok_or_else(|| {
+								invalid(format!("\"comment\" needs a \"{}\" string", key))
+							})
+					};
+					(part("start")?, part("prefix")?, part("end")?)
+				}
+				Some(_) => return Err(invalid("\"comment\" must be a style or a table".into())),
+				None => return Err(invalid("it needs a \"comment\" style".into())),
+			};
+
+			rules.push(Rule {
+				extensions,
+				file_names,
+				comment_style: Some(comment_style),
+				footer: true,
+				structured: None,
+				interpreters,
+				origin: "language definition",
+			});
+		}
+		Ok(Rules(rules))
+	}
+
+	/// Add the rules of `other` after these, so they only apply where none of these do.
+	pub fn extend(&mut self, other: Rules) {
+		self.0.extend(other.0);
+	}
+
+	/// Whether any rule names interpreters, so that shebang lines are worth reading.
+	pub fn has_interpreters(&self) -> bool {
+		self.0.iter().any(|rule| !rule.interpreters.is_empty())
+	}
+
+	/// The first rule whose language's interpreter is `program`.
+	pub fn resolve_interpreter(&self, program: &str) -> Option<&Rule> {
+		self.0
+			.iter()
+			.find(|rule| rule.interpreters.iter().any(|name| name == program))
+	}
+
+	/// The rule that applies to `path`: the first one naming its file name, or failing
+	/// that, the first one covering its extension.
+	pub fn resolve(&self, path: &Path) -> Option<&Rule> {
+		let file_name = path.file_name()?.to_string_lossy();
+		let by_name = self
+			.0
+			.iter()
+			.find(|rule| rule.file_names.iter().any(|name| *name == file_name));
+		by_name.or_else(|| {
+			let ext = path.extension()?.to_string_lossy().to_lowercase();
+			self.0.iter().find(|rule| rule.extensions.contains(&ext))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::TempDir;
+
+	const STYLES: &[(&str, &str, &str)] = &[("/*", " * ", " */"), ("#", "# ", "#")];
+
+	#[test]
+	fn file_names_win_over_extensions() {
+		let dir = TempDir::new();
+		let path = dir.write(
+			"rules.json",
+			r##"{ "rules": [
+				{ "extensions": [".RS"], "footer": false },
+				{ "files": ["build.rs"], "comment": "#" }
+			] }"##,
+		);
+		let rules = Rules::load(&path, STYLES).unwrap();
+		let lib = rules.resolve(Path::new("src/lib.rs")).unwrap();
+		assert!(!lib.footer && lib.comment_style.is_none());
+		let build = rules.resolve(Path::new("build.rs")).unwrap();
+		assert!(build.footer && build.comment_style == Some(("#", "# ", "#")));
+		assert!(rules.resolve(Path::new("main.py")).is_none());
+	}
+
+	#[test]
+	fn invalid_rules_are_reported_with_their_position() {
+		let dir = TempDir::new();
+		for (rules, message) in [
+			(
+				r##"{ "rules": [{ "comment": "#" }] }"##,
+				"rule 1 matches no files",
+			),
+			(
+				r##"{ "rules": [{ "files": ["a"] }, { "files": ["b"], "comment": "@@" }] }"##,
+				"rule 2: unknown comment style @@",
+			),
+			(r##"{ "rule": [] }"##, "expected a \"rules\" array"),
+		] {
+			let path = dir.write("rules.json", rules);
+			let error = Rules::load(&path, STYLES).err().unwrap();
+			assert!(error.to_string().ends_with(message), "{}", error);
+		}
+	}
+}
+
+/*
+ * License:
+ * Copyright (c) 2025 Eric Hernandez
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
@@ -0,0 +1,1216 @@
+/* Copyright (c) 2025 Eric Hernandez  */
+
+//! Runs of the built binary over scratch directories, for behavior that only shows on
+//! the command line.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fresh directory under the system temp dir, removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+	fn new() -> TempDir {
+		static COUNT: AtomicUsize = AtomicUsize::new(0);
+		let dir = std::env::temp_dir().join(format!(
+			"copywriter-cli-{}-{}",
+			std::process::id(),
+			COUNT.fetch_add(1, Ordering::Relaxed)
+		));
+		fs::create_dir_all(&dir).unwrap();
+		TempDir(dir)
+	}
+
+	fn path(&self) -> &Path {
+		&self.0
+	}
+
+	/// Write `content` to `name` below the directory, creating parent directories.
+	fn write(&self, name: &str, content: &str) -> PathBuf {
+		let path = self.0.join(name);
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(&path, content).unwrap();
+		path
+	}
+
+	fn read(&self, name: &str) -> String {
+		fs::read_to_string(self.0.join(name)).unwrap()
+	}
+}
+
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.0);
+	}
+}
+
+/// A command running the binary in `dir`, unaffected by the caller's author settings.
+fn copywriter(dir: &TempDir) -> Command {
+	let mut command = Command::new(env!("CARGO_BIN_EXE_copywriter"));
+	command
+		.current_dir(dir.path())
+		.env_remove("COPYWRITER_AUTHOR")
+		.env_remove("SOURCE_DATE_EPOCH");
+	command
+}
+
+/// Run the binary in `dir` with `args`.
+fn run(dir: &TempDir, args: &[&str]) -> Output {
+	copywriter(dir).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+	String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+	String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn license_names_pick_the_footer_license() {
+	let dir = TempDir::new();
+	dir.write("COPYING", "GNU GENERAL PUBLIC LICENSE\n");
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("src/main.rs", "fn main() {}\n");
+	let output = run(
+		&dir,
+		&[
+			"-a",
+			"Jane Doe",
+			"--license-names",
+			"COPYING,LICENSE",
+			"src",
+		],
+	);
+	assert!(output.status.success());
+	let content = dir.read("src/main.rs");
+	assert!(content.contains("GNU GENERAL PUBLIC LICENSE"));
+	assert!(!content.contains("MIT License"));
+	assert!(stdout(&output).contains("Updated"));
+}
+
+#[test]
+fn deterministic_runs_are_byte_identical() {
+	let runs: Vec<(String, Vec<String>)> = (0..2)
+		.map(|_| {
+			let dir = TempDir::new();
+			dir.write("LICENSE", "MIT License\n");
+			for name in ["src/b.rs", "src/a.py", "src/nested/c.js", "d.sh"] {
+				dir.write(name, "x\n");
+			}
+			let output = copywriter(&dir)
+				.env("SOURCE_DATE_EPOCH", "1262304000")
+				.args(["-a", "Jane Doe", "--deterministic", "."])
+				.output()
+				.unwrap();
+			assert!(output.status.success());
+			let contents = ["src/b.rs", "src/a.py", "src/nested/c.js", "d.sh"]
+				.iter()
+				.map(|name| dir.read(name))
+				.collect();
+			(stdout(&output), contents)
+		})
+		.collect();
+	assert_eq!(runs[0], runs[1]);
+	assert!(runs[0].1[0].starts_with("/* Copyright (c) 2010 Jane Doe"));
+}
+
+#[test]
+fn holders_report_flags_holders_without_the_current_year() {
+	let dir = TempDir::new();
+	dir.write("old.py", "# Copyright (c) 2015-2019 Old Corp\nx = 1\n");
+	dir.write("new.py", "# Copyright (c) 2019 Old Corp\nx = 1\n");
+	// The footer's copy of the license names its licensor, who is no holder.
+	dir.write(
+		"cur.rs",
+		"// Copyright (c) 2018-2099 Current Inc\nfn a() {}\n\n\
+		 /*\n * License:\n * MIT License\n *\n * Copyright (c) 2020 Licensor\n */",
+	);
+	let output = stdout(&run(&dir, &["--report-holders", "."]));
+	assert_eq!(output, "Current Inc: 2099\nOld Corp: 2019 (stale)\n");
+}
+
+#[test]
+fn watch_updates_changed_files_and_honors_ignore_files() {
+	use std::io::{BufRead, BufReader};
+	use std::process::Stdio;
+	use std::time::{Duration, Instant};
+
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write(".gitignore", "generated/\n");
+	for subdir in ["generated", "src"] {
+		fs::create_dir_all(dir.path().join(subdir)).unwrap();
+	}
+	let mut watcher = copywriter(&dir)
+		.args(["-a", "Jane Doe", "-l", "LICENSE", "--watch", "."])
+		.stdout(Stdio::piped())
+		.spawn()
+		.unwrap();
+	let mut lines = BufReader::new(watcher.stdout.take().unwrap()).lines();
+	let ready = lines.find(|line| line.as_ref().is_ok_and(|line| line.starts_with("Watching")));
+	assert!(ready.is_some());
+
+	dir.write("generated/gen.py", "x = 1\n");
+	dir.write("src/new.py", "x = 1\n");
+	let deadline = Instant::now() + Duration::from_secs(10);
+	while !dir.read("src/new.py").contains("Copyright") && Instant::now() < deadline {
+		std::thread::sleep(Duration::from_millis(50));
+	}
+	// Give the ignored file's events the same chance to be (wrongly) acted on.
+	std::thread::sleep(Duration::from_millis(500));
+	watcher.kill().unwrap();
+	watcher.wait().unwrap();
+
+	assert!(dir.read("src/new.py").starts_with("# Copyright (c) "));
+	assert_eq!(dir.read("generated/gen.py"), "x = 1\n");
+}
+
+#[test]
+fn author_comes_from_the_environment_then_the_config_file() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write(".copywriter.toml", "author = \"Config Author\"\n");
+	dir.write("env.rs", "fn a() {}\n");
+	dir.write("config.rs", "fn a() {}\n");
+	let output = copywriter(&dir)
+		.env("COPYWRITER_AUTHOR", "Env Author")
+		.args(["-l", "LICENSE", "env.rs"])
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+	assert!(dir.read("env.rs").starts_with("/* Copyright (c) "));
+	assert!(dir
+		.read("env.rs")
+		.lines()
+		.next()
+		.unwrap()
+		.ends_with(" Env Author  */"));
+	assert!(run(&dir, &["-l", "LICENSE", "config.rs"]).status.success());
+	assert!(dir
+		.read("config.rs")
+		.lines()
+		.next()
+		.unwrap()
+		.ends_with(" Config Author  */"));
+}
+
+#[test]
+fn lang_filter_processes_only_that_language() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	for name in ["a.py", "sub/b.py", "c.rs", "d.js"] {
+		dir.write(name, "x\n");
+	}
+	let output = run(
+		&dir,
+		&["-a", "Jane Doe", "-l", "LICENSE", "--lang", "python", "."],
+	);
+	assert!(output.status.success());
+	assert!(dir.read("a.py").starts_with("# Copyright (c) "));
+	assert!(dir.read("sub/b.py").starts_with("# Copyright (c) "));
+	assert_eq!(dir.read("c.rs"), "x\n");
+	assert_eq!(dir.read("d.js"), "x\n");
+}
+
+#[test]
+fn changed_license_reports_stale_footers() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("a.rs", "fn a() {}\n");
+	dir.write("b.py", "x = 1\n");
+	let args = ["-a", "Jane Doe", "-l", "LICENSE", "--cache", "cache.json"];
+	assert!(run(&dir, &[&args[..], &["."]].concat()).status.success());
+	let check = [&args[..], &["--check-stale-footers", "."]].concat();
+	let output = run(&dir, &check);
+	assert!(output.status.success());
+	assert!(stdout(&output).contains("License unchanged"));
+
+	dir.write("LICENSE", "Apache License\n");
+	let annotated = dir.read("a.rs");
+	let output = run(&dir, &check);
+	assert_eq!(output.status.code(), Some(1));
+	let report = stdout(&output);
+	assert!(report.contains("Stale footer: ./a.rs"), "{}", report);
+	assert!(report.contains("Stale footer: ./b.py"), "{}", report);
+	assert_eq!(dir.read("a.rs"), annotated);
+}
+
+#[test]
+fn preview_shows_the_first_n_files_without_writing() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	let names = ["a.py", "b.py", "c.py", "d.py", "e.py"];
+	for name in names {
+		dir.write(name, "x = 1\n");
+	}
+	let output = run(
+		&dir,
+		&["-a", "Jane Doe", "-l", "LICENSE", "--preview", "2", "."],
+	);
+	assert!(output.status.success());
+	let preview = stdout(&output);
+	assert_eq!(preview.matches("Would update: ").count(), 2, "{}", preview);
+	assert_eq!(
+		preview
+			.lines()
+			.filter(|line| line.starts_with("--- "))
+			.count(),
+		2
+	);
+	for name in names {
+		assert_eq!(dir.read(name), "x = 1\n");
+	}
+}
+
+#[test]
+fn list_changed_prints_bare_paths_of_files_that_would_change() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("a.py", "x = 1\n");
+	dir.write("sub/b.rs", "fn b() {}\n");
+	dir.write("done.py", "x = 1\n");
+	assert!(run(&dir, &["-a", "Jane Doe", "-l", "LICENSE", "done.py"])
+		.status
+		.success());
+	let done = dir.read("done.py");
+
+	let output = run(
+		&dir,
+		&["-a", "Jane Doe", "-l", "LICENSE", "--list-changed", "."],
+	);
+	assert!(output.status.success());
+	let mut listed: Vec<_> = stdout(&output).lines().map(str::to_string).collect();
+	listed.sort();
+	assert_eq!(listed, ["./a.py", "./sub/b.rs"]);
+	assert!(stdout(&output).ends_with('\n'));
+	assert_eq!(dir.read("a.py"), "x = 1\n");
+	assert_eq!(dir.read("done.py"), done);
+}
+
+#[test]
+fn git_root_points_git_at_another_repository() {
+	let dir = TempDir::new();
+	let repo = dir.path().join("repo");
+	fs::create_dir_all(&repo).unwrap();
+	let git = |args: &[&str]| {
+		let status = Command::new("git")
+			.arg("-C")
+			.arg(&repo)
+			.args(args)
+			.status()
+			.unwrap();
+		assert!(status.success());
+	};
+	git(&["init", "-q"]);
+	git(&["config", "user.name", "Repo Author"]);
+	dir.write("elsewhere/LICENSE", "MIT License\n");
+	dir.write("elsewhere/a.py", "x = 1\n");
+
+	let output = run(
+		&dir,
+		&["--git-root", "repo", "-l", "elsewhere/LICENSE", "elsewhere"],
+	);
+	assert!(output.status.success());
+	let header = dir.read("elsewhere/a.py");
+	assert!(
+		header.lines().next().unwrap().ends_with(" Repo Author #"),
+		"{}",
+		header
+	);
+
+	let output = run(
+		&dir,
+		&[
+			"--git-root",
+			"elsewhere",
+			"-l",
+			"elsewhere/LICENSE",
+			"elsewhere",
+		],
+	);
+	assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn bom_files_get_one_header_over_repeated_runs() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("bom.rs", "\u{feff}fn a() {}\n");
+	let args = ["-a", "Jane Doe", "-l", "LICENSE", "bom.rs"];
+	assert!(run(&dir, &args).status.success());
+	let annotated = dir.read("bom.rs");
+	assert!(
+		annotated.starts_with("\u{feff}/* Copyright (c) "),
+		"{}",
+		annotated
+	);
+	assert!(run(&dir, &args).status.success());
+	assert_eq!(dir.read("bom.rs"), annotated);
+	assert_eq!(annotated.matches("Copyright").count(), 1, "{}", annotated);
+	assert!(run(&dir, &[&args[..], &["--check"]].concat())
+		.status
+		.success());
+}
+
+#[test]
+fn fail_on_change_exit_codes() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("a.py", "x = 1\n");
+	let args = |extra: &[&'static str]| [&["-a", "Jane Doe", "-l", "LICENSE"], extra].concat();
+
+	assert_eq!(
+		run(&dir, &args(&["--fail-on-change", "a.py"]))
+			.status
+			.code(),
+		Some(1)
+	);
+	assert_eq!(dir.read("a.py"), "x = 1\n");
+	assert_eq!(
+		run(&dir, &args(&["--dry-run", "a.py"])).status.code(),
+		Some(0)
+	);
+	assert_eq!(dir.read("a.py"), "x = 1\n");
+
+	assert_eq!(run(&dir, &args(&["a.py"])).status.code(), Some(0));
+	assert_eq!(
+		run(&dir, &args(&["--fail-on-change", "a.py"]))
+			.status
+			.code(),
+		Some(0)
+	);
+	assert_eq!(
+		run(&dir, &args(&["--fail-on-change", "missing.py"]))
+			.status
+			.code(),
+		Some(2)
+	);
+}
+
+#[test]
+fn paths_restrict_the_check_to_matching_files() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("team-a/a.py", "x = 1\n");
+	dir.write("team-b/b.py", "x = 1\n");
+	let check = [
+		"-a",
+		"Jane Doe",
+		"-l",
+		"LICENSE",
+		"--check",
+		"--paths",
+		"team-b/**",
+	];
+	let output = run(&dir, &[&check[..], &["."]].concat());
+	assert_eq!(output.status.code(), Some(1));
+	let report = stdout(&output);
+	assert!(report.contains("team-b/b.py"), "{}", report);
+	assert!(!report.contains("team-a"), "{}", report);
+
+	// Composed with --exclude, nothing owned is left to check.
+	let output = run(
+		&dir,
+		&[&check[..], &["--exclude", "team-b/b.py", "."]].concat(),
+	);
+	assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+}
+
+#[test]
+fn summary_json_counts_the_run() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("a.py", "x = 1\n");
+	dir.write("b.py", "x = 1\n");
+	dir.write("c.rs", "fn c() {}\n");
+	dir.write("bin.rs", "\0\0\0");
+	assert!(run(&dir, &["-a", "Jane Doe", "-l", "LICENSE", "b.py"])
+		.status
+		.success());
+	let args = [
+		"-a",
+		"Jane Doe",
+		"-l",
+		"LICENSE",
+		"--summary-json",
+		"summary.json",
+		".",
+	];
+	assert!(run(&dir, &args).status.success());
+	let summary: serde_json::Value = serde_json::from_str(&dir.read("summary.json")).unwrap();
+	assert_eq!(
+		summary,
+		serde_json::json!({
+			"totals": { "updated": 2, "unchanged": 1, "skipped": 1, "error": 0 },
+			"extensions": {
+				"py": { "updated": 1, "unchanged": 1, "skipped": 0, "error": 0 },
+				"rs": { "updated": 1, "unchanged": 0, "skipped": 1, "error": 0 },
+			},
+		})
+	);
+}
+
+#[test]
+fn footer_once_per_directory_puts_one_footer_in_each_directory() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	let files = [
+		"docs/a.py",
+		"docs/index.py",
+		"docs/api/c.py",
+		"docs/api/d.py",
+	];
+	for name in files {
+		dir.write(name, "x = 1\n");
+	}
+	let args = [
+		"-a",
+		"Jane Doe",
+		"-l",
+		"LICENSE",
+		"--footer-once-per-directory",
+	];
+	let args = [&args[..], &["--footer-file", "index.py", "docs"]].concat();
+	assert!(run(&dir, &args).status.success());
+	for name in files {
+		assert!(dir.read(name).starts_with("# Copyright (c) "), "{}", name);
+	}
+	let footers: Vec<_> = files
+		.into_iter()
+		.filter(|name| dir.read(name).contains("License:"))
+		.collect();
+	assert_eq!(footers, ["docs/index.py", "docs/api/c.py"]);
+
+	// A re-run neither adds footers elsewhere nor drops the existing ones.
+	let before: Vec<_> = files.iter().map(|name| dir.read(name)).collect();
+	assert!(run(&dir, &args).status.success());
+	let after: Vec<_> = files.iter().map(|name| dir.read(name)).collect();
+	assert_eq!(before, after);
+}
+
+#[test]
+fn allowed_licenses_flags_files_declaring_other_licenses() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("gpl.py", "# SPDX-License-Identifier: GPL-3.0\nx = 1\n");
+	dir.write("mit.py", "# SPDX-License-Identifier: MIT\nx = 1\n");
+	let args = [
+		"-a",
+		"Jane Doe",
+		"-l",
+		"LICENSE",
+		"--allowed-licenses",
+		"MIT,Apache-2.0",
+	];
+	let output = run(&dir, &[&args[..], &["."]].concat());
+	assert_eq!(output.status.code(), Some(1));
+	let report = stdout(&output);
+	assert!(
+		report.contains("Disallowed license GPL-3.0: ./gpl.py"),
+		"{}",
+		report
+	);
+	assert!(!report.contains("mit.py"), "{}", report);
+	assert_eq!(
+		dir.read("gpl.py"),
+		"# SPDX-License-Identifier: GPL-3.0\nx = 1\n"
+	);
+
+	assert_eq!(
+		run(&dir, &[&args[..], &["mit.py"]].concat()).status.code(),
+		Some(0)
+	);
+}
+
+#[test]
+fn diff_scope_restricts_the_diff_to_one_region() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("a.py", "x = 1\n");
+	let diff = |scope| {
+		let args = [
+			"-a",
+			"Jane Doe",
+			"-l",
+			"LICENSE",
+			"--dry-run",
+			"--diff",
+			"--diff-scope",
+			scope,
+			"a.py",
+		];
+		let output = run(&dir, &args);
+		assert!(output.status.success());
+		stdout(&output)
+	};
+	let header = diff("header");
+	assert!(header.contains("+# Copyright (c) "), "{}", header);
+	assert!(!header.contains("License:"), "{}", header);
+	assert_eq!(header.matches("@@ ").count(), 1, "{}", header);
+	let footer = diff("footer");
+	assert!(footer.contains("+# License:"), "{}", footer);
+	assert!(!footer.contains("+# Copyright"), "{}", footer);
+	let both = diff("both");
+	assert!(both.contains("+# Copyright (c) ") && both.contains("+# License:"));
+	assert_eq!(dir.read("a.py"), "x = 1\n");
+}
+
+#[test]
+fn scan_encoding_reports_latin1_files_without_touching_them() {
+	let dir = TempDir::new();
+	let latin1 = b"# Gr\xfc\xdfe aus M\xfcnchen, sch\xf6ne Stra\xdfe\nx = 1\n";
+	fs::write(dir.path().join("latin1.py"), latin1).unwrap();
+	dir.write("utf8.py", "# Grüße\nx = 1\n");
+	let output = run(&dir, &["--scan-encoding", "."]);
+	assert!(output.status.success());
+	let report = stdout(&output);
+	assert!(report.contains("./utf8.py: UTF-8\n"), "{}", report);
+	assert!(
+		report.contains("./latin1.py: windows-1252 (not UTF-8)\n"),
+		"{}",
+		report
+	);
+	assert!(stderr(&output).contains("1 file(s) are not valid UTF-8"));
+	assert_eq!(fs::read(dir.path().join("latin1.py")).unwrap(), latin1);
+}
+
+#[test]
+fn stdin_path_picks_the_comment_style_of_its_extension() {
+	use std::io::Write;
+	use std::process::Stdio;
+
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	let mut child = copywriter(&dir)
+		.args([
+			"-a",
+			"Jane Doe",
+			"-l",
+			"LICENSE",
+			"--stdin",
+			"--stdin-path",
+			"src/foo.py",
+		])
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child
+		.stdin
+		.take()
+		.unwrap()
+		.write_all(b"print(1)\n")
+		.unwrap();
+	let output = child.wait_with_output().unwrap();
+	assert!(output.status.success());
+	let content = stdout(&output);
+	assert!(content.starts_with("# Copyright (c) "), "{}", content);
+	assert!(content.contains("\nprint(1)\n"));
+	assert!(
+		content.contains("#\n# License:\n# MIT License\n#"),
+		"{}",
+		content
+	);
+	assert!(!dir.path().join("src").exists());
+}
+
+#[test]
+fn report_licenses_flags_directories_without_one() {
+	let dir = TempDir::new();
+	dir.write("b/LICENSE", "MIT License\n");
+	for name in ["top.py", "a/x.py", "b/c/y.py"] {
+		dir.write(name, "x = 1\n");
+	}
+	let output = run(&dir, &["--report-licenses", "."]);
+	assert!(output.status.success());
+	let mut report: Vec<_> = stdout(&output).lines().map(str::to_string).collect();
+	report.sort();
+	assert_eq!(report, ["./a: missing", "./b/c: ./b/LICENSE", ".: missing"]);
+	assert_eq!(dir.read("top.py"), "x = 1\n");
+}
+
+#[test]
+fn scan_duplicate_headers_collapses_every_affected_file() {
+	let dir = TempDir::new();
+	let rust = "/* Copyright (c) 2019 Jane Doe  */\n/* Copyright (c) 2024-2025 Jane Doe  */\n\nfn a() {}\n";
+	let python = "# Copyright (c) 2020 Acme #\n\n# Copyright (c) 2020 Acme #\nx = 1\n";
+	dir.write("a.rs", rust);
+	dir.write("sub/b.py", python);
+	dir.write("clean.py", "# Copyright (c) 2020 Acme #\nx = 1\n");
+
+	let output = run(&dir, &["--scan-duplicate-headers", "--dry-run", "."]);
+	assert!(output.status.success());
+	assert_eq!(dir.read("a.rs"), rust);
+	assert_eq!(stdout(&output).matches("Duplicate headers: ").count(), 2);
+
+	let output = run(&dir, &["--scan-duplicate-headers", "."]);
+	assert!(output.status.success());
+	let report = stdout(&output);
+	assert!(
+		report.contains("Collapsed duplicate headers: ./a.rs\n"),
+		"{}",
+		report
+	);
+	assert!(
+		report.contains("Collapsed duplicate headers: ./sub/b.py\n"),
+		"{}",
+		report
+	);
+	assert!(!report.contains("clean.py"));
+	let a = dir.read("a.rs");
+	assert!(
+		a.starts_with("/* Copyright (c) 2019, 2024-2025 Jane Doe  */\n"),
+		"{}",
+		a
+	);
+	assert_eq!(a.matches("Copyright").count(), 1);
+	assert_eq!(dir.read("sub/b.py").matches("Copyright").count(), 1);
+
+	// Safe to run again: nothing left to collapse.
+	assert_eq!(stdout(&run(&dir, &["--scan-duplicate-headers", "."])), "");
+}
+
+#[test]
+fn skipped_report_lists_a_binary_file_with_its_reason() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("a.rs", "fn a() {}\n");
+	dir.write("blob.rs", "\0\0binary");
+	fs::write(dir.path().join("latin1.rs"), b"// caf\xe9\n").unwrap();
+	let args = [
+		"-a",
+		"Jane Doe",
+		"-l",
+		"LICENSE",
+		"--skipped-report",
+		"skipped.txt",
+		".",
+	];
+	assert!(run(&dir, &args).status.success());
+	let mut report: Vec<_> = dir
+		.read("skipped.txt")
+		.lines()
+		.map(str::to_string)
+		.collect();
+	report.sort();
+	assert_eq!(report, ["./blob.rs: binary", "./latin1.rs: not-utf8"]);
+}
+
+#[test]
+fn rules_file_handles_each_file_type_per_its_rule() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write(
+		"rules.json",
+		r#"{ "rules": [
+			{ "extensions": ["rs"], "comment": "//", "footer": false },
+			{ "extensions": ["md"], "comment": "<!--" }
+		] }"#,
+	);
+	dir.write("src/lib.rs", "pub fn a() {}\n");
+	dir.write("README.md", "# Title\n");
+	let args = [
+		"-a",
+		"Jane Doe",
+		"-l",
+		"LICENSE",
+		"--rules",
+		"rules.json",
+		".",
+	];
+	assert!(run(&dir, &args).status.success());
+
+	let rust = dir.read("src/lib.rs");
+	assert!(rust.starts_with("// Copyright (c) "), "{}", rust);
+	assert!(!rust.contains("License:"), "{}", rust);
+	let markdown = dir.read("README.md");
+	assert!(markdown.starts_with("<!-- Copyright (c) "), "{}", markdown);
+	assert!(
+		markdown.contains("<!--\n License:\n MIT License\n-->"),
+		"{}",
+		markdown
+	);
+	assert!(!dir.read("rules.json").contains("Copyright"));
+
+	assert!(run(&dir, &args).status.success());
+	assert_eq!(dir.read("src/lib.rs"), rust);
+	assert_eq!(dir.read("README.md"), markdown);
+}
+
+#[test]
+fn time_budget_stops_early_and_the_cache_resumes_the_rest() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	let names = ["a.py", "b.py", "c.py"];
+	for name in names {
+		dir.write(name, "x = 1\n");
+	}
+	let annotated = |name| dir.read(name).starts_with("# Copyright (c) ");
+	let args = ["-a", "Jane Doe", "-l", "LICENSE", "--time-budget", "0ms"];
+
+	let output = run(&dir, &[&args[..], &["--check", "."]].concat());
+	assert_eq!(output.status.code(), Some(1));
+	assert!(stderr(&output).contains("1 file(s) processed, 2 remaining"));
+
+	let resumable = [&args[..], &["--cache", "cache.json", "."]].concat();
+	let output = run(&dir, &resumable);
+	assert!(output.status.success());
+	assert!(stderr(&output).contains("Time budget exceeded: 1 file(s) processed, 2 remaining"));
+	assert_eq!(names.iter().filter(|name| annotated(name)).count(), 1);
+	let output = run(&dir, &resumable);
+	assert!(stderr(&output).contains("1 file(s) processed, 1 remaining"));
+	let output = run(&dir, &resumable);
+	assert!(!stderr(&output).contains("Time budget exceeded"));
+	assert!(names.iter().all(|name| annotated(name)));
+}
+
+#[test]
+fn ignore_files_and_globs_prune_the_walk() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write(".gitignore", "vendor/\n");
+	for name in [
+		"src/b.py",
+		"src/b_test.py",
+		"target/debug/a.py",
+		"vendor/v.py",
+		"docs/d.py",
+	] {
+		dir.write(name, "x = 1\n");
+	}
+	let args = ["-a", "Jane Doe", "-l", "LICENSE", "--dry-run"];
+	let globs = [
+		"--exclude",
+		"target/",
+		"--exclude",
+		"**/*_test.py",
+		"--include",
+		"src/**",
+	];
+	let output = run(
+		&dir,
+		&[&args[..], &globs, &["--include", "target/**", "."]].concat(),
+	);
+	assert!(output.status.success());
+	let report = stdout(&output);
+	let mut skipped: Vec<_> = report
+		.lines()
+		.filter_map(|line| line.strip_prefix("Skipping ignored path: "))
+		.collect();
+	skipped.sort();
+	// Excludes win over includes, and an excluded directory is reported once, not per file.
+	assert_eq!(
+		skipped,
+		["./docs/d.py", "./src/b_test.py", "./target", "./vendor"]
+	);
+	let updated: Vec<_> = report
+		.lines()
+		.filter_map(|line| line.strip_prefix("Would update: "))
+		.collect();
+	assert_eq!(updated, ["./src/b.py"]);
+
+	let output = run(&dir, &[&args[..], &["--no-ignore", "."]].concat());
+	let report = stdout(&output);
+	assert_eq!(report.matches("Would update: ").count(), 5, "{}", report);
+	assert!(!report.contains("Skipping ignored path"));
+}
+
+#[test]
+fn compare_two_authors_classifies_each_file() {
+	let dir = TempDir::new();
+	dir.write("acme.py", "# Copyright (c) 2020 Acme #\nx = 1\n");
+	dir.write("globex.py", "# Copyright (c) 2020 Globex #\nx = 1\n");
+	dir.write(
+		"sub/both.py",
+		"# Copyright (c) 2020 Acme #\n# Copyright 2021 Globex\nx = 1\n",
+	);
+	dir.write("other.py", "# Copyright (c) 2020 Initech #\nx = 1\n");
+	// A license footer naming Globex does not make the file Globex's.
+	dir.write(
+		"footer.rs",
+		"/* Copyright (c) 2020 Acme  */\n\nfn a() {}\n\n/*\n * License:\n * Copyright (c) 2019 Globex\n */\n",
+	);
+	let output = run(&dir, &["--compare-two-authors", "Acme", "Globex", "."]);
+	assert!(output.status.success());
+	let report = stdout(&output);
+	for line in [
+		"./acme.py: Acme\n",
+		"./globex.py: Globex\n",
+		"./sub/both.py: both\n",
+		"./other.py: neither\n",
+		"./footer.rs: Acme\n",
+	] {
+		assert!(report.contains(line), "{}", report);
+	}
+	assert!(report
+		.ends_with("\nHeld by Acme: 2\nHeld by Globex: 1\nHeld by both: 1\nHeld by neither: 1\n"));
+}
+
+#[test]
+fn incremental_runs_skip_files_untouched_since_the_last_run() {
+	use std::time::{Duration, SystemTime};
+
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("a.py", "x = 1\n");
+	dir.write("b.py", "x = 1\n");
+	let args = ["-a", "Jane Doe", "-l", "LICENSE", "--cache", "cache.json"];
+	let incremental = [&args[..], &["--incremental", "."]].concat();
+	assert!(run(&dir, &incremental).status.success());
+	assert!(dir.read("a.py").starts_with("# Copyright (c) "));
+
+	// a.py loses its header but keeps an old mtime, so only a full run would see it.
+	let a = dir.write("a.py", "x = 1\n");
+	let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+	fs::File::options()
+		.write(true)
+		.open(&a)
+		.unwrap()
+		.set_modified(old)
+		.unwrap();
+	std::thread::sleep(Duration::from_millis(20));
+	dir.write("b.py", "y = 2\n");
+
+	let output = run(&dir, &incremental);
+	assert!(output.status.success());
+	assert_eq!(stdout(&output), "Updated: ./b.py\n");
+	assert_eq!(dir.read("a.py"), "x = 1\n");
+	assert!(dir.read("b.py").starts_with("# Copyright (c) "));
+
+	// Other settings invalidate the recorded run.
+	let output = run(
+		&dir,
+		&[&args[..], &["--timestamp", "--incremental", "."]].concat(),
+	);
+	assert!(output.status.success());
+	assert!(dir.read("a.py").starts_with("# Copyright (c) "));
+}
+
+#[test]
+fn check_only_extensions_leaves_other_files_uncounted() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	for name in ["a.rs", "b.go", "c.py", "d.js"] {
+		dir.write(name, "x\n");
+	}
+	let args = [
+		"-a",
+		"Jane Doe",
+		"-l",
+		"LICENSE",
+		"--check",
+		"--check-only-extensions",
+		"rs,.GO",
+		".",
+	];
+	let output = run(&dir, &args);
+	assert_eq!(output.status.code(), Some(1));
+	let mut failing: Vec<_> = stdout(&output)
+		.lines()
+		.filter_map(|line| line.strip_prefix("Needs update: "))
+		.map(str::to_string)
+		.collect();
+	failing.sort();
+	assert_eq!(failing, ["./a.rs", "./b.go"]);
+	assert!(stderr(&output).contains("2 file(s) failed the check"));
+}
+
+#[test]
+fn verbose_reports_the_comment_style_and_why() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("tool", "#!/usr/bin/env python3\nprint(1)\n");
+	dir.write("lib.rs", "fn a() {}\n");
+	let args = ["-a", "Jane Doe", "-l", "LICENSE", "--verbose", "--dry-run"];
+	let report = stdout(&run(&dir, &[&args[..], &["tool"]].concat()));
+	assert!(
+		report.contains("Comment style for tool: (\"#\", \"# \", \"#\") from shebang\n"),
+		"{}",
+		report
+	);
+	let report = stdout(&run(&dir, &[&args[..], &["lib.rs"]].concat()));
+	assert!(
+		report.contains("Comment style for lib.rs: (\"/*\", \" * \", \" */\") from extension\n"),
+		"{}",
+		report
+	);
+	let quiet = stdout(&run(
+		&dir,
+		&["-a", "Jane Doe", "-l", "LICENSE", "--dry-run", "tool"],
+	));
+	assert!(!quiet.contains("Comment style"));
+}
+
+#[test]
+fn apply_selected_changes_only_the_chosen_plan_entries() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	let names = ["a.py", "b.py", "c.py", "d.py"];
+	for name in names {
+		dir.write(name, "x = 1\n");
+	}
+	let args = ["-a", "Jane Doe", "-l", "LICENSE", "--plan", "plan.json"];
+	let output = run(&dir, &[&args[..], &["--dry-run", "."]].concat());
+	assert!(output.status.success());
+	assert!(names.iter().all(|name| dir.read(name) == "x = 1\n"));
+	let plan: serde_json::Value = serde_json::from_str(&dir.read("plan.json")).unwrap();
+	let planned: Vec<&str> = plan["files"]
+		.as_array()
+		.unwrap()
+		.iter()
+		.map(|file| file.as_str().unwrap())
+		.collect();
+	assert_eq!(planned.len(), 4);
+
+	let output = run(
+		&dir,
+		&[&args[..], &["--apply-selected", "1,3-4", "."]].concat(),
+	);
+	assert!(output.status.success());
+	for (i, file) in planned.iter().enumerate() {
+		let name = file.trim_start_matches("./");
+		assert_eq!(
+			dir.read(name).starts_with("# Copyright (c) "),
+			i != 1,
+			"{}",
+			name
+		);
+	}
+}
+
+#[test]
+fn header_within_lines_flags_headers_buried_too_deep() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	let doc: String = (1..=9).map(|i| format!("//! Doc line {}.\n", i)).collect();
+	dir.write(
+		"deep.rs",
+		&format!("{}/* Copyright (c) 2020 Jane Doe  */\n\nfn a() {{}}\n", doc),
+	);
+	dir.write("none.rs", "fn a() {}\n");
+	dir.write("top.rs", "fn a() {}\n");
+	let args = ["-a", "Jane Doe", "-l", "LICENSE"];
+	assert!(run(&dir, &[&args[..], &["top.rs"]].concat())
+		.status
+		.success());
+
+	let check = [&args[..], &["--check", "--header-within-lines", "5", "."]].concat();
+	let output = run(&dir, &check);
+	assert_eq!(output.status.code(), Some(1));
+	let report = stdout(&output);
+	for file in ["./deep.rs", "./none.rs"] {
+		let flagged = format!("Header not within the first 5 lines: {}\n", file);
+		assert!(report.contains(&flagged), "{}", report);
+	}
+	assert!(!report.contains("./top.rs"), "{}", report);
+}
+
+#[test]
+fn relicense_replaces_every_footer_and_keeps_headers() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("LICENSE-APACHE", "Apache License\nVersion 2.0\n");
+	dir.write("a.rs", "// SPDX-License-Identifier: MIT\nfn a() {}\n");
+	dir.write("sub/b.py", "x = 1\n");
+	dir.write("c.py", "# Copyright (c) 2019 Other Corp #\nx = 1\n");
+	assert!(run(&dir, &["-a", "Jane Doe", "-l", "LICENSE", "."])
+		.status
+		.success());
+	let headers: Vec<String> = ["a.rs", "sub/b.py", "c.py"]
+		.iter()
+		.map(|name| dir.read(name).lines().next().unwrap().to_string())
+		.collect();
+
+	let args = [
+		"-a",
+		"Jane Doe",
+		"--relicense",
+		"LICENSE-APACHE",
+		"--spdx-id",
+		"Apache-2.0",
+		".",
+	];
+	assert!(run(&dir, &args).status.success());
+	for (name, header) in ["a.rs", "sub/b.py", "c.py"].iter().zip(&headers) {
+		let content = dir.read(name);
+		assert!(content.starts_with(header.as_str()), "{}", content);
+		assert!(content.contains("Apache License") && content.contains("Version 2.0"));
+		assert!(!content.contains("MIT License"), "{}", content);
+		assert_eq!(content.matches("License:").count(), 1, "{}", content);
+	}
+	assert!(dir
+		.read("a.rs")
+		.contains("SPDX-License-Identifier: Apache-2.0\n"));
+	assert!(dir
+		.read("c.py")
+		.contains("# Copyright (c) 2019 Other Corp #\n"));
+}
+
+#[test]
+fn lang_defs_add_languages_at_runtime() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write(
+		"langs/foo.toml",
+		"extensions = [\"foo\"]\ncomment = { start = \"##\", prefix = \"## \", end = \"##\" }\ninterpreters = [\"foo\"]\n",
+	);
+	dir.write("src/main.foo", "print 1\n");
+	dir.write("src/tool", "#!/usr/bin/env foo\nprint 1\n");
+	dir.write("src/notes", "plain text\n");
+	let args = [
+		"-a",
+		"Jane Doe",
+		"-l",
+		"LICENSE",
+		"--lang-defs",
+		"langs",
+		"src",
+	];
+	assert!(run(&dir, &args).status.success());
+
+	let foo = dir.read("src/main.foo");
+	assert!(foo.starts_with("## Copyright (c) "), "{}", foo);
+	assert!(
+		foo.contains("##\n## License:\n## MIT License\n##"),
+		"{}",
+		foo
+	);
+	// An extensionless script is picked up by its shebang, which stays on line one.
+	let tool = dir.read("src/tool");
+	assert!(
+		tool.starts_with("#!/usr/bin/env foo\n\n## Copyright (c) "),
+		"{}",
+		tool
+	);
+	assert_eq!(dir.read("src/notes"), "plain text\n");
+	assert!(!dir.read("langs/foo.toml").contains("Copyright"));
+}
+
+#[test]
+fn lang_bazel_walks_build_and_workspace_files_by_name() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	let bazel = ["WORKSPACE", "pkg/BUILD", "pkg/BUILD.bazel", "pkg/defs.bzl"];
+	for name in bazel.iter().chain(&["pkg/main.py"]) {
+		dir.write(name, "x = 1\n");
+	}
+	let output = run(
+		&dir,
+		&["-a", "Jane Doe", "-l", "LICENSE", "--lang", "bazel", "."],
+	);
+	assert!(output.status.success());
+	for name in bazel {
+		assert!(dir.read(name).starts_with("# Copyright (c) "), "{}", name);
+	}
+	assert_eq!(dir.read("pkg/main.py"), "x = 1\n");
+}
+
+#[test]
+fn audit_json_describes_every_file() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	let rust =
+		"/* Copyright (c) 2019-2024 Jane Doe  */\n// SPDX-License-Identifier: MIT\n\nfn a() {}\n\n\
+	            /*\n * License:\n * Copyright (c) 2010 Licensor\n */\n";
+	dir.write("a.rs", rust);
+	dir.write("sub/bare.py", "x = 1\n");
+	dir.write("blob.rs", "\0\0");
+	let output = run(&dir, &["--audit-json", "audit.json", "."]);
+	assert!(output.status.success());
+	assert_eq!(dir.read("a.rs"), rust);
+	let audit: serde_json::Value = serde_json::from_str(&dir.read("audit.json")).unwrap();
+	assert_eq!(
+		audit,
+		serde_json::json!({ "files": [
+			{
+				"path": "./a.rs",
+				"skipped": null,
+				"header": true,
+				"footer": true,
+				"holders": [{ "holder": "Jane Doe", "years": "2019-2024", "last_year": 2024 }],
+				"spdx_ids": ["MIT"],
+				"license_file": "./LICENSE",
+			},
+			{
+				"path": "./blob.rs",
+				"skipped": "binary",
+				"header": null,
+				"footer": null,
+				"holders": null,
+				"spdx_ids": null,
+				"license_file": "./LICENSE",
+			},
+			{
+				"path": "./sub/bare.py",
+				"skipped": null,
+				"header": false,
+				"footer": false,
+				"holders": [],
+				"spdx_ids": [],
+				"license_file": "./LICENSE",
+			},
+		] })
+	);
+}
+
+#[test]
+fn parallel_license_resolution_reports_the_same_licenses() {
+	let dir = TempDir::new();
+	dir.write("LICENSE", "MIT License\n");
+	dir.write("vendor/LICENSE", "Apache License\n");
+	for i in 0..20 {
+		dir.write(&format!("pkg{}/a.py", i), "x = 1\n");
+		dir.write(&format!("vendor/lib{}/b.py", i), "x = 1\n");
+	}
+	let report = |args: &[&str]| {
+		let output = run(&dir, args);
+		assert!(output.status.success(), "{}", stderr(&output));
+		let mut lines: Vec<_> = stdout(&output).lines().map(str::to_string).collect();
+		lines.sort();
+		lines
+	};
+	let sequential = report(&["--report-licenses", "."]);
+	assert_eq!(sequential.len(), 40);
+	assert!(sequential.contains(&"./vendor/lib3: ./vendor/LICENSE".to_string()));
+	assert!(sequential.contains(&"./pkg3: ./LICENSE".to_string()));
+	let parallel = ["--report-licenses", "--parallel-license-resolution", "."];
+	assert_eq!(report(&parallel), sequential);
+
+	let audit = ["--audit-json", "audit.json", "."];
+	assert!(run(&dir, &audit).status.success());
+	let sequential = dir.read("audit.json");
+	let parallel = [&audit[..2], &["--parallel-license-resolution", "."]].concat();
+	assert!(run(&dir, &parallel).status.success());
+	assert_eq!(dir.read("audit.json"), sequential);
+	// The flag only applies to the per-directory license lookups.
+	assert!(!run(&dir, &["--parallel-license-resolution", "."])
+		.status
+		.success());
+}
+
+/*
+ * License:
+ * Copyright (c) 2025 Eric Hernandez
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */